@@ -0,0 +1,437 @@
+//! In-silico PCR (`amplicon`).
+//!
+//! Reads primer pairs from a TSV, finds their **binding sites** on every contig of a reference (IUPAC-degenerate
+//! primers, up to `--max-mismatches` mismatches each) and emits the **amplicons**: the template from the 5' end of
+//! the forward primer's site to the 5' end of the reverse primer's site, `--min-length`..=`--max-length` long, in
+//! either orientation. Amplicons are written in the forward primer's orientation, so they start with (the template
+//! of) the forward primer; contigs are treated as linear. A template that both orientations amplify (palindromic
+//! primers, or identical forward and reverse primers) is one amplicon, in the orientation with fewer mismatches or,
+//! on a tie, with the forward primer on the plus strand.
+//!
+//! ### Primer TSV
+//! `name  forward  reverse  [copies]`, primers 5'→3' (the reverse primer as ordered, binding the minus strand).
+//! Lines starting with `#` and a header line are skipped. `copies` (default `--copies`) is the number of molecules
+//! of each amplicon of the pair.
+//!
+//! ### Output
+//! Without `--n`, every copy of every amplicon is written once (FASTA, or FASTQ reads with `--error-model`). With
+//! `--n`, that many molecules are drawn at random, weighted by copy number. Reads reuse the `seq_sample` machinery:
+//! `--error-model` simulates one read per amplicon, `--paired` reads `--read-length` bases from both ends. Headers
+//! follow `seq_sample` (`src=`, `range=`, `strand=`) and add `amplicon=<primer> mismatches=<forward>,<reverse>`.
+//! A report (stderr, or `--report` TSV) lists the sites and amplicons of each primer pair on each contig.
+//!
+//! ### Example
+//! ```text
+//! limpet amplicon -r genomes.fa -p 16s_primers.tsv --max-mismatches 2 --copies 10 -o amplicons.fa --report hits.tsv
+//! ```
+
+use crate::faidx::Reference;
+use crate::fragment::{fragment_name, write_pairs, write_reads, Fragment, Origin, ReadOutput};
+use crate::seqio::{create_output, create_output_with, open_input, reverse_complement, Codec, FastaWriter, SeqMode};
+use crate::simulate::{ErrorModel, ErrorProfile, PairNaming};
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug, Clone)]
+pub struct AmpliconArgs {
+    /// Reference FASTA (optionally compressed; `-` for stdin)
+    #[arg(short = 'r', long = "reference", value_name = "INPUT")]
+    pub reference: PathBuf,
+
+    /// Primer pairs as TSV: name, forward, reverse and optionally copies
+    #[arg(short = 'p', long = "primers", value_name = "TSV")]
+    pub primers: PathBuf,
+
+    /// Output path, FASTA (FASTQ with `--error-model` or `--paired`); `-` for stdout
+    #[arg(short = 'o', long = "output", value_name = "OUTPUT")]
+    pub output: PathBuf,
+
+    /// Maximum mismatches between a primer and its binding site (IUPAC codes match any of their bases)
+    #[arg(long = "max-mismatches", value_name = "INT", default_value_t = 2)]
+    pub max_mismatches: usize,
+
+    /// Minimum amplicon length, primers included
+    #[arg(long = "min-length", value_name = "INT", default_value_t = 50)]
+    pub min_length: usize,
+
+    /// Maximum amplicon length, primers included
+    #[arg(long = "max-length", value_name = "INT", default_value_t = 2000)]
+    pub max_length: usize,
+
+    /// Copies of each amplicon for primer pairs without a copies column
+    #[arg(long = "copies", value_name = "INT", default_value_t = 1)]
+    pub copies: usize,
+
+    /// Draw this many molecules at random, weighted by copy number, instead of writing every copy
+    #[arg(short = 'n', long = "n", value_name = "INT")]
+    pub n: Option<usize>,
+
+    /// Write the binding sites and amplicons per primer pair and contig to this TSV (default: stderr)
+    #[arg(long = "report", value_name = "TSV")]
+    pub report: Option<PathBuf>,
+
+    /// Simulate sequencing errors and qualities with this profile, writing FASTQ reads
+    #[arg(long = "error-model", value_enum, value_name = "PROFILE")]
+    pub error_model: Option<ErrorProfile>,
+
+    /// Mean per-base error rate (default: 0.005 for illumina, 0.08 for long-read)
+    #[arg(long = "error-rate", value_name = "FLOAT", requires = "error_model")]
+    pub error_rate: Option<f64>,
+
+    /// Write every simulated error to this TSV (read, contig, ref_pos, read_pos, type, ref, alt)
+    #[arg(long = "errors-out", value_name = "TSV", requires = "error_model")]
+    pub errors_out: Option<PathBuf>,
+
+    /// Paired-end mode: write R1 to `--output` and R2 to `--output2` (FASTQ)
+    #[arg(long = "paired", requires_all = ["output2", "read_length"])]
+    pub paired: bool,
+
+    /// R2 output path in paired mode
+    #[arg(long = "output2", value_name = "OUTPUT", requires = "paired")]
+    pub output2: Option<PathBuf>,
+
+    /// Bases read from each end of the amplicon in paired mode
+    #[arg(long = "read-length", value_name = "INT", requires = "paired")]
+    pub read_length: Option<usize>,
+
+    /// Read-name style in paired mode: `slash` (`/1`, `/2`) or `casava` (`1:N:0:1`)
+    #[arg(long = "read-names", value_enum, value_name = "STYLE", default_value = "slash")]
+    pub read_names: PairNaming,
+
+    /// Optional RNG seed for reproducibility
+    #[arg(long = "seed", value_name = "INT")]
+    pub seed: Option<u64>,
+
+    /// Output compression (default: from the output file extension; plain for stdout)
+    #[arg(long = "compress", value_enum, value_name = "CODEC")]
+    pub compress: Option<Codec>,
+}
+
+/// A primer pair; sequences are 5'→3' and upper case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Primer {
+    pub name: String,
+    pub forward: Vec<u8>,
+    /// Reverse primer as ordered (it binds the minus strand)
+    pub reverse: Vec<u8>,
+    /// Copies of each amplicon (`None`: the `--copies` default)
+    pub copies: Option<usize>,
+}
+
+/// Where one primer pair amplifies one contig.
+#[derive(Debug, Clone, Default)]
+pub struct PrimerHits {
+    /// Forward primer sites on either strand
+    pub forward_sites: usize,
+    /// Reverse primer sites on either strand
+    pub reverse_sites: usize,
+    /// Amplicons as fragments (`seq` in the forward primer's orientation, [`Origin::Amplicon`])
+    pub amplicons: Vec<Fragment>,
+}
+
+/// Bit mask of the nucleotides an IUPAC code stands for (`A`=1, `C`=2, `G`=4, `T`/`U`=8); 0 for anything else.
+fn iupac_mask(b: u8) -> u8 {
+    match b.to_ascii_uppercase() {
+        b'A' => 1,
+        b'C' => 2,
+        b'G' => 4,
+        b'T' | b'U' => 8,
+        b'R' => 1 | 4,
+        b'Y' => 2 | 8,
+        b'S' => 2 | 4,
+        b'W' => 1 | 8,
+        b'K' => 4 | 8,
+        b'M' => 1 | 2,
+        b'B' => 2 | 4 | 8,
+        b'D' => 1 | 4 | 8,
+        b'H' => 1 | 2 | 8,
+        b'V' => 1 | 2 | 4,
+        b'N' => 15,
+        _ => 0,
+    }
+}
+
+/// Start positions on `template` where `primer` (IUPAC) matches with at most `max_mismatches`, with the mismatch
+/// count. Template bases other than `ACGT` always mismatch.
+pub fn binding_sites(template: &[u8], primer: &[u8], max_mismatches: usize) -> Vec<(usize, usize)> {
+    let masks: Vec<u8> = primer.iter().map(|&b| iupac_mask(b)).collect();
+    let mut out = Vec::new();
+    if primer.is_empty() || primer.len() > template.len() { return out; }
+    for p in 0..=template.len() - primer.len() {
+        let mut mm = 0;
+        for (&t, &m) in template[p..].iter().zip(&masks) {
+            let t = iupac_mask(t);
+            if t.count_ones() != 1 || t & m == 0 {
+                mm += 1;
+                if mm > max_mismatches { break; }
+            }
+        }
+        if mm <= max_mismatches {
+            out.push((p, mm));
+        }
+    }
+    out
+}
+
+/// Find the amplicons of `primer` on contig `name`: a primer site on one strand facing a site of the other primer on
+/// the other strand, `min_len..=max_len` apart (outer ends). Every such pair of sites gives an amplicon, once per
+/// `(start, end)` even when both orientations produce it.
+pub fn find_amplicons(name: &str, seq: &[u8], primer: &Primer, max_mismatches: usize, min_len: usize, max_len: usize) -> PrimerHits {
+    let (fwd, rev) = (&primer.forward, &primer.reverse);
+    // Sites on the forward strand of the template, for each primer and its reverse complement
+    let fwd_plus = binding_sites(seq, fwd, max_mismatches);
+    let fwd_minus = binding_sites(seq, &reverse_complement(fwd), max_mismatches);
+    let rev_plus = binding_sites(seq, rev, max_mismatches);
+    let rev_minus = binding_sites(seq, &reverse_complement(rev), max_mismatches);
+
+    let mut amplicons = Vec::new();
+    // `left` primes the forward strand at its site, `right` (reverse-complemented) closes the product
+    let mut pair = |left: &[(usize, usize)], right: &[(usize, usize)], right_len: usize, reverse: bool| {
+        for &(start, left_mm) in left {
+            let lo = right.partition_point(|&(q, _)| q + right_len < start + min_len);
+            for &(q, right_mm) in right[lo..].iter().take_while(|&&(q, _)| q + right_len <= start + max_len) {
+                if q < start { continue; }
+                let end = q + right_len;
+                let template = &seq[start..end];
+                let (forward_mismatches, reverse_mismatches) = if reverse { (right_mm, left_mm) } else { (left_mm, right_mm) };
+                amplicons.push(Fragment {
+                    contig: name.to_string(),
+                    start,
+                    end,
+                    reverse,
+                    seq: if reverse { reverse_complement(template) } else { template.to_vec() },
+                    circular_len: None,
+                    origin: Origin::Amplicon { primer: primer.name.clone(), forward_mismatches, reverse_mismatches },
+                    umi: None,
+                });
+            }
+        }
+    };
+    pair(&fwd_plus, &rev_minus, rev.len(), false);
+    pair(&rev_plus, &fwd_minus, fwd.len(), true);
+    let mismatches = |f: &Fragment| match f.origin {
+        Origin::Amplicon { forward_mismatches, reverse_mismatches, .. } => forward_mismatches + reverse_mismatches,
+        _ => 0,
+    };
+    amplicons.sort_by_key(|f| (f.start, f.end, mismatches(f), f.reverse));
+    amplicons.dedup_by_key(|f| (f.start, f.end));
+    PrimerHits {
+        forward_sites: fwd_plus.len() + fwd_minus.len(),
+        reverse_sites: rev_plus.len() + rev_minus.len(),
+        amplicons,
+    }
+}
+
+/// Read a primer TSV (`name forward reverse [copies]`).
+pub fn read_primers<P: AsRef<Path>>(path: P) -> Result<Vec<Primer>> {
+    let path = path.as_ref();
+    let mut out = Vec::new();
+    for (i, line) in open_input(path)?.lines().enumerate() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() { continue; }
+        let cols: Vec<&str> = line.split('\t').map(str::trim).collect();
+        let at = || format!("{}: line {}", path.display(), i + 1);
+        if cols.len() < 3 {
+            return Err(anyhow!("{}: expected name, forward and reverse primer columns", at()));
+        }
+        let is_primer = |p: &str| !p.is_empty() && p.bytes().all(|b| iupac_mask(b) != 0);
+        if !is_primer(cols[1]) || !is_primer(cols[2]) {
+            // A header line
+            if out.is_empty() && cols[1].eq_ignore_ascii_case("forward") { continue; }
+            return Err(anyhow!("{}: primers must be IUPAC nucleotide codes", at()));
+        }
+        let copies = match cols.get(3).filter(|c| !c.is_empty()) {
+            Some(c) => Some(c.parse().ok().filter(|&n: &usize| n > 0).with_context(|| format!("{}: invalid copies '{}'", at(), c))?),
+            None => None,
+        };
+        out.push(Primer {
+            name: cols[0].to_string(),
+            forward: cols[1].to_ascii_uppercase().into_bytes(),
+            reverse: cols[2].to_ascii_uppercase().into_bytes(),
+            copies,
+        });
+    }
+    if out.is_empty() {
+        return Err(anyhow!("No primer pairs found in {}", path.display()));
+    }
+    Ok(out)
+}
+
+/// Write the per-primer, per-contig report: site and amplicon counts and the amplicon lengths. Primer pairs without
+/// any site get one line with contig `.`.
+fn write_report<W: Write>(w: &mut W, primers: &[Primer], hits: &[Vec<(String, PrimerHits)>]) -> Result<()> {
+    writeln!(w, "#primer\tcontig\tforward_sites\treverse_sites\tamplicons\tlengths")?;
+    for (primer, per_contig) in primers.iter().zip(hits) {
+        if per_contig.is_empty() {
+            writeln!(w, "{}\t.\t0\t0\t0\t.", primer.name)?;
+        }
+        for (contig, h) in per_contig {
+            let lengths: Vec<String> = h.amplicons.iter().map(|f| f.seq.len().to_string()).collect();
+            let lengths = if lengths.is_empty() { ".".to_string() } else { lengths.join(",") };
+            writeln!(w, "{}\t{}\t{}\t{}\t{}\t{}", primer.name, contig, h.forward_sites, h.reverse_sites, h.amplicons.len(), lengths)?;
+        }
+    }
+    Ok(())
+}
+
+/// Execute the `amplicon` subcommand.
+/// Scans every contig for every primer pair, reports the hits and writes the amplicon molecules or reads.
+pub fn run(args: AmpliconArgs) -> Result<()> {
+    if args.min_length > args.max_length {
+        return Err(anyhow!("--min-length must be <= --max-length"));
+    }
+    if args.copies == 0 || args.n == Some(0) {
+        return Err(anyhow!("--copies and --n must be greater than 0"));
+    }
+    if args.read_length == Some(0) {
+        return Err(anyhow!("--read-length must be greater than 0"));
+    }
+    let primers = read_primers(&args.primers)?;
+    let model = match args.error_model {
+        Some(profile) => {
+            let model = ErrorModel::new(profile);
+            Some(match args.error_rate {
+                Some(rate) => model.with_rate(rate)?,
+                None => model,
+            })
+        }
+        None => None,
+    };
+    let mut rng: StdRng = match args.seed {
+        Some(s) => StdRng::seed_from_u64(s),
+        None => StdRng::from_rng(thread_rng()).context("Failed to initialize RNG")?,
+    };
+
+    let mut reference = Reference::open(&args.reference, SeqMode::Normalize)?;
+    let mut hits: Vec<Vec<(String, PrimerHits)>> = vec![Vec::new(); primers.len()];
+    for i in 0..reference.len() {
+        let seq = reference.fetch(i, 0, reference.seq_len(i))?;
+        let name = reference.name(i).to_string();
+        for (p, primer) in primers.iter().enumerate() {
+            let h = find_amplicons(&name, &seq, primer, args.max_mismatches, args.min_length, args.max_length);
+            if h.forward_sites + h.reverse_sites > 0 {
+                hits[p].push((name.clone(), h));
+            }
+        }
+    }
+    match &args.report {
+        Some(path) => {
+            let mut w = create_output(path)?;
+            write_report(&mut w, &primers, &hits)?;
+            w.close()?;
+        }
+        None => write_report(&mut std::io::stderr(), &primers, &hits)?,
+    }
+    for (primer, per_contig) in primers.iter().zip(&hits) {
+        if per_contig.iter().all(|(_, h)| h.amplicons.is_empty()) {
+            eprintln!("Warning: primer pair '{}' gives no amplicons", primer.name);
+        }
+    }
+
+    // Every amplicon with its copy number
+    let mut amplicons: Vec<(Fragment, usize)> = Vec::new();
+    for (primer, per_contig) in primers.iter().zip(hits) {
+        let copies = primer.copies.unwrap_or(args.copies);
+        amplicons.extend(per_contig.into_iter().flat_map(|(_, h)| h.amplicons).map(|f| (f, copies)));
+    }
+    if amplicons.is_empty() {
+        return Err(anyhow!("No amplicons found in {}", args.reference.display()));
+    }
+    let molecules: Vec<Fragment> = match args.n {
+        Some(n) => {
+            let index = WeightedIndex::new(amplicons.iter().map(|(_, c)| *c)).context("Invalid copy numbers")?;
+            (0..n).map(|_| amplicons[index.sample(&mut rng)].0.clone()).collect()
+        }
+        None => amplicons.iter().flat_map(|(f, c)| std::iter::repeat_n(f, *c).cloned()).collect(),
+    };
+    eprintln!("Found {} amplicon(s); emitting {} molecule(s)", amplicons.len(), molecules.len());
+
+    let out = ReadOutput {
+        output: &args.output,
+        output2: args.output2.as_deref(),
+        compress: args.compress,
+        errors_out: args.errors_out.as_deref(),
+        read_length: args.read_length,
+        read_names: args.read_names,
+    };
+    if args.paired {
+        let (lo, hi) = molecules.iter().fold((usize::MAX, 0), |(lo, hi), f| (lo.min(f.seq.len()), hi.max(f.seq.len())));
+        return write_pairs(&out, &format!("{}..{} bp (amplicons)", lo, hi), &molecules, model.as_ref(), &mut rng);
+    }
+    if let Some(model) = model {
+        return write_reads(&out, &molecules, &model, &mut rng);
+    }
+    let mut writer = FastaWriter::new(create_output_with(&args.output, args.compress)?, 80);
+    for (i, f) in molecules.iter().enumerate() {
+        writer.write_record(&format!("{} {}", fragment_name(i + 1), f.describe()), &f.seq)?;
+    }
+    writer.finish()?.close()?;
+    eprintln!("Wrote {} amplicons to {}", molecules.len(), args.output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_args;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn degenerate_primers_amplify_both_orientations() {
+        assert_eq!(binding_sites(b"ACGTACGGAC", b"ACR", 0), [(0, 0), (4, 0)]);
+        assert_eq!(binding_sites(b"ACGTNCGGAC", b"ACGTA", 1), [(0, 1)]);
+
+        let left = b"GATTACAGATTACA";
+        let right = b"CCCCGGGGAAAATTTT";
+        let middle = b"ACGTGCTAGCTAGGATCGATCGGCTAGCTAG";
+        let insert: Vec<u8> = [&left[..], middle, right].concat();
+        let seq: Vec<u8> = [&b"TTTTTTTTTT"[..], &insert, b"AAAAAAAAAA", &reverse_complement(&insert), b"GGGG"].concat();
+        // One mismatch in the forward primer, a degenerate base in the reverse primer
+        let primer = Primer {
+            name: "p".into(),
+            forward: b"GATTACAGATTACT".to_vec(),
+            reverse: reverse_complement(b"CCCCGGGGAAAATTTY"),
+            copies: None,
+        };
+        assert!(find_amplicons("c", &seq, &primer, 0, 1, 1000).amplicons.is_empty());
+        let h = find_amplicons("c", &seq, &primer, 1, 1, 1000);
+        assert_eq!((h.forward_sites, h.reverse_sites), (2, 2));
+        assert_eq!(h.amplicons.len(), 2);
+        for f in &h.amplicons {
+            assert_eq!(f.seq, insert);
+            assert!(matches!(f.origin, Origin::Amplicon { forward_mismatches: 1, reverse_mismatches: 0, .. }));
+        }
+        assert_eq!((h.amplicons[0].start, h.amplicons[0].reverse), (10, false));
+        assert!(h.amplicons[1].reverse);
+        assert!(find_amplicons("c", &seq, &primer, 1, 1, insert.len() - 1).amplicons.is_empty());
+    }
+
+    #[test]
+    fn copies_set_the_molecule_counts() {
+        let dir = tempdir().unwrap();
+        let reference = dir.path().join("ref.fa");
+        let primers = dir.path().join("primers.tsv");
+        let output = dir.path().join("out.fa");
+        let report = dir.path().join("report.tsv");
+        fs::write(&reference, ">a\nTTTGATTACAGGGGGGGGGGTGTAATCTTT\n>b\nCCCCCCCCCC\n").unwrap();
+        fs::write(&primers, "name\tforward\treverse\tcopies\np1\tGATTAC\tGATTAC\t3\n").unwrap();
+        let path = |p: &Path| p.to_str().unwrap().to_string();
+        let (reference, primers, out, rep) = (path(&reference), path(&primers), path(&output), path(&report));
+        let args: AmpliconArgs = parse_args(&[
+            "--reference", &reference, "--primers", &primers, "--output", &out, "--report", &rep, "--max-mismatches", "0",
+            "--min-length", "10", "--max-length", "100", "--seed", "1",
+        ]);
+        run(args).unwrap();
+        let out = fs::read_to_string(&output).unwrap();
+        let headers: Vec<&str> = out.lines().filter(|l| l.starts_with('>')).collect();
+        // The palindromic pair amplifies the same interval in both orientations: one amplicon, three copies
+        assert_eq!(headers.len(), 3);
+        assert!(headers.iter().all(|h| h.ends_with("src=a range=4..27 len=24 strand=+ amplicon=p1 mismatches=0,0")));
+        assert_eq!(fs::read_to_string(&report).unwrap().lines().nth(1), Some("p1\ta\t2\t2\t1\t24"));
+    }
+}
@@ -0,0 +1,362 @@
+//! Minimal **GTF/GFF3** reader and transcript expression.
+//!
+//! Only `exon` features are used: GTF exons are grouped by their `transcript_id` (gene from `gene_id`), GFF3 exons
+//! by their `Parent` (gene from the transcript feature's own `Parent`, or the transcript itself). The dialect is
+//! detected per line from the attribute column (`key "value"` vs `key=value`). Coordinates are **1-based inclusive**
+//! in the file and 0-based half-open in [`Transcript::exons`]; overlapping or adjacent exons are merged.
+//!
+//! Expression comes from a TSV of TPMs ([`read_tpm`]) or a random log-normal profile ([`lognormal_tpm`]).
+//!
+//! A window of a transcript is read through its exons with [`Transcript::splice`] and becomes an RNA-seq
+//! [`Fragment`] with [`Transcript::fragment`]; which transcript and window are drawn is up to the sampler.
+
+use crate::fragment::{Fragment, LiftBlock, Origin};
+use crate::seqio::{open_input, reverse_complement};
+use anyhow::{anyhow, Context, Result};
+use rand::Rng;
+use rand_distr::{Distribution, LogNormal};
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+
+/// A spliced transcript.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcript {
+    pub id: String,
+    pub gene: String,
+    pub chrom: String,
+    /// On the minus strand
+    pub reverse: bool,
+    /// Exons as 0-based half-open genomic intervals, sorted and non-overlapping
+    pub exons: Vec<(usize, usize)>,
+}
+
+impl Transcript {
+    /// Spliced length.
+    pub fn len(&self) -> usize {
+        self.exons.iter().map(|(s, e)| e - s).sum()
+    }
+
+    /// True if the transcript has no bases.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Genomic intervals of spliced offsets `[start, end)`, counted from the leftmost exon base (genomic order,
+    /// whatever the strand).
+    pub fn blocks(&self, start: usize, end: usize) -> Vec<(usize, usize)> {
+        let mut out = Vec::new();
+        let mut offset = 0;
+        for &(s, e) in &self.exons {
+            let (lo, hi) = (start.max(offset), end.min(offset + e - s));
+            if lo < hi {
+                out.push((s + lo - offset, s + hi - offset));
+            }
+            offset += e - s;
+        }
+        out
+    }
+
+    /// Bases of the spliced offsets `[start, end)` in transcript (genomic) order, read from `fetch(start, end)` of
+    /// the transcript's contig, with one [`LiftBlock`] per exon they cross.
+    pub fn splice<F>(&self, start: usize, end: usize, mut fetch: F) -> Result<(Vec<u8>, Vec<LiftBlock>)>
+    where
+        F: FnMut(usize, usize) -> Result<Vec<u8>>,
+    {
+        let mut seq = Vec::with_capacity(end - start);
+        let mut lift = Vec::new();
+        for (s, e) in self.blocks(start, end) {
+            lift.push(LiftBlock { offset: seq.len(), ref_pos: s, ref_len: e - s });
+            seq.extend(fetch(s, e)?);
+        }
+        Ok((seq, lift))
+    }
+
+    /// Fragment of spliced bases `seq` lifted by `lift` (see [`Transcript::splice`]), read from the `sense` strand
+    /// of the transcript or its antisense. The sense strand of a minus-strand transcript is the genomic minus strand.
+    pub fn fragment(&self, seq: Vec<u8>, lift: Vec<LiftBlock>, sense: bool) -> Fragment {
+        let reverse = sense == self.reverse;
+        let last = lift[lift.len() - 1];
+        Fragment {
+            contig: self.chrom.clone(),
+            start: lift[0].ref_pos,
+            end: last.ref_pos + last.ref_len,
+            reverse,
+            seq: if reverse { reverse_complement(&seq) } else { seq },
+            circular_len: None,
+            origin: Origin::Transcript { id: self.id.clone(), gene: self.gene.clone(), blocks: lift },
+            umi: None,
+        }
+    }
+}
+
+/// Expressed transcripts sorted by their longest piece, longest first, with cumulative `TPM × length` weights, so
+/// that one draw picks a transcript with a piece at least as long as a fragment.
+pub(crate) struct TranscriptTable {
+    /// Transcript indices, longest piece first
+    order: Vec<usize>,
+    /// Longest piece in `order`
+    lens: Vec<usize>,
+    /// Cumulative weights in `order`
+    cumulative: Vec<f64>,
+    /// Pieces of each transcript (in transcript coordinates) that windows may be drawn from
+    pieces: Vec<Vec<(usize, usize)>>,
+}
+
+impl TranscriptTable {
+    /// Table of `transcripts` expressed at `tpm`, whose windows may only be drawn from `pieces` (e.g. the stretches
+    /// free of ambiguous runs); transcripts without TPM or pieces are never picked.
+    pub(crate) fn new(transcripts: &[Transcript], tpm: &[f64], pieces: Vec<Vec<(usize, usize)>>) -> Self {
+        let longest = |i: usize| pieces[i].iter().map(|&(s, e)| e - s).max().unwrap_or(0);
+        let mut order: Vec<usize> = (0..transcripts.len()).filter(|&i| tpm[i] > 0.0 && longest(i) > 0).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(longest(i)));
+        let lens: Vec<usize> = order.iter().map(|&i| longest(i)).collect();
+        let cumulative = order
+            .iter()
+            .scan(0.0, |acc, &i| {
+                *acc += tpm[i] * transcripts[i].len() as f64;
+                Some(*acc)
+            })
+            .collect();
+        TranscriptTable { order, lens, cumulative, pieces }
+    }
+
+    /// Longest piece of any expressed transcript (0 if none).
+    pub(crate) fn longest(&self) -> usize {
+        self.lens.first().copied().unwrap_or(0)
+    }
+
+    /// Pick a transcript with a piece of at least `len` with probability proportional to its weight, and a uniformly
+    /// random start of a `len` window within its pieces.
+    pub(crate) fn pick<R: Rng>(&self, len: usize, rng: &mut R) -> Option<(usize, usize)> {
+        let k = self.lens.partition_point(|&l| l >= len);
+        if k == 0 { return None; }
+        let u = rng.gen::<f64>() * self.cumulative[k - 1];
+        let j = self.cumulative[..k].partition_point(|&c| c <= u);
+        let i = self.order[j.min(k - 1)];
+        let fits = || self.pieces[i].iter().filter(|&&(s, e)| e - s >= len);
+        let mut u = rng.gen_range(0..fits().map(|&(s, e)| e - s - len + 1).sum::<usize>());
+        for &(s, e) in fits() {
+            if u <= e - s - len {
+                return Some((i, s + u));
+            }
+            u -= e - s - len + 1;
+        }
+        unreachable!("the draw falls within the fitting pieces")
+    }
+}
+
+/// Split a GTF (`key "value"; ...`) or GFF3 (`key=value;...`) attribute column into key/value pairs.
+fn parse_attributes(col: &str) -> Vec<(&str, &str)> {
+    let gff3 = col.find([' ', '=']).is_some_and(|i| col.as_bytes()[i] == b'=');
+    col.split(';')
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .filter_map(|a| if gff3 { a.split_once('=') } else { a.split_once(' ') })
+        .map(|(k, v)| (k.trim(), v.trim().trim_matches('"')))
+        .collect()
+}
+
+/// Read the transcripts of a GTF or GFF3 file, in order of first appearance.
+pub fn read_annotation<P: AsRef<Path>>(path: P) -> Result<Vec<Transcript>> {
+    let path = path.as_ref();
+    let mut transcripts: Vec<Transcript> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    // GFF3 `ID` -> first `Parent`, to find the gene of each transcript
+    let mut parents: HashMap<String, String> = HashMap::new();
+    for (i, line) in open_input(path)?.lines().enumerate() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() { continue; }
+        let at = || format!("{}: line {}", path.display(), i + 1);
+        let cols: Vec<&str> = line.split('\t').collect();
+        if cols.len() < 9 {
+            return Err(anyhow!("{}: expected 9 tab-separated GTF/GFF3 columns", at()));
+        }
+        let attrs = parse_attributes(cols[8]);
+        let get = |key: &str| attrs.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+        if cols[2] != "exon" {
+            if let (Some(id), Some(parent)) = (get("ID"), get("Parent")) {
+                parents.insert(id.to_string(), parent.split(',').next().unwrap_or(parent).to_string());
+            }
+            continue;
+        }
+        let start: usize = cols[3].parse().map_err(|_| anyhow!("{}: invalid start '{}'", at(), cols[3]))?;
+        let end: usize = cols[4].parse().map_err(|_| anyhow!("{}: invalid end '{}'", at(), cols[4]))?;
+        if start == 0 || end < start {
+            return Err(anyhow!("{}: invalid exon {}..{} (1-based, inclusive)", at(), start, end));
+        }
+        // GTF names one transcript; a GFF3 exon may be shared by several
+        let owners: Vec<(&str, Option<&str>)> = match (get("transcript_id"), get("Parent")) {
+            (Some(t), _) => vec![(t, get("gene_id"))],
+            (None, Some(p)) => p.split(',').map(|t| (t, None)).collect(),
+            (None, None) => return Err(anyhow!("{}: exon without transcript_id or Parent", at())),
+        };
+        for (id, gene) in owners {
+            let k = *index.entry(id.to_string()).or_insert_with(|| {
+                transcripts.push(Transcript {
+                    id: id.to_string(),
+                    gene: gene.unwrap_or_default().to_string(),
+                    chrom: cols[0].to_string(),
+                    reverse: cols[6] == "-",
+                    exons: Vec::new(),
+                });
+                transcripts.len() - 1
+            });
+            let t = &mut transcripts[k];
+            if t.chrom != cols[0] {
+                return Err(anyhow!("{}: transcript '{}' has exons on '{}' and '{}'", at(), id, t.chrom, cols[0]));
+            }
+            t.exons.push((start - 1, end));
+        }
+    }
+    for t in &mut transcripts {
+        if t.gene.is_empty() {
+            t.gene = parents.get(&t.id).unwrap_or(&t.id).clone();
+        }
+        t.exons.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(t.exons.len());
+        for &(s, e) in &t.exons {
+            match merged.last_mut() {
+                Some(last) if s <= last.1 => last.1 = last.1.max(e),
+                _ => merged.push((s, e)),
+            }
+        }
+        t.exons = merged;
+    }
+    Ok(transcripts)
+}
+
+/// Read a TSV of transcript TPMs. The value is taken from the second column, or from a `TPM` column when the first
+/// line is a header (as in salmon's `quant.sf`). Lines starting with `#` are skipped.
+pub fn read_tpm<P: AsRef<Path>>(path: P) -> Result<HashMap<String, f64>> {
+    let path = path.as_ref();
+    let mut out = HashMap::new();
+    let mut column = 1;
+    let mut first = true;
+    for (i, line) in open_input(path)?.lines().enumerate() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() { continue; }
+        let cols: Vec<&str> = line.split('\t').collect();
+        if first {
+            first = false;
+            if cols.get(column).is_none_or(|v| v.trim().parse::<f64>().is_err()) {
+                column = cols.iter().position(|c| c.trim().eq_ignore_ascii_case("tpm")).unwrap_or(1);
+                continue;
+            }
+        }
+        let value = cols.get(column).map(|v| v.trim()).unwrap_or_default();
+        let tpm: f64 = value
+            .parse()
+            .ok()
+            .filter(|v: &f64| v.is_finite() && *v >= 0.0)
+            .with_context(|| format!("{}: line {}: invalid TPM '{}'", path.display(), i + 1, value))?;
+        out.insert(cols[0].trim().to_string(), tpm);
+    }
+    Ok(out)
+}
+
+/// Random expression profile: independent log-normal(0, `sigma`) draws scaled to sum to one million.
+pub fn lognormal_tpm<R: Rng>(n: usize, sigma: f64, rng: &mut R) -> Result<Vec<f64>> {
+    let d = LogNormal::new(0.0, sigma).map_err(|e| anyhow!("--expression-sigma {}: {}", sigma, e))?;
+    let raw: Vec<f64> = (0..n).map(|_| d.sample(rng)).collect();
+    let total: f64 = raw.iter().sum();
+    Ok(raw.iter().map(|x| x * 1e6 / total).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn reads_gtf_and_gff3_transcripts() {
+        let dir = tempdir().unwrap();
+        let gtf = dir.path().join("a.gtf");
+        fs::write(
+            &gtf,
+            "#comment\n\
+             c1\tsrc\tgene\t1\t100\t.\t+\t.\tgene_id \"G1\";\n\
+             c1\tsrc\texon\t50\t60\t.\t-\t.\tgene_id \"G1\"; transcript_id \"T1\";\n\
+             c1\tsrc\texon\t10\t20\t.\t-\t.\tgene_id \"G1\"; transcript_id \"T1\";\n\
+             c1\tsrc\texon\t15\t30\t.\t-\t.\tgene_id \"G1\"; transcript_id \"T1\";\n",
+        )
+        .unwrap();
+        let t = read_annotation(&gtf).unwrap();
+        assert_eq!(t.len(), 1);
+        assert_eq!((t[0].id.as_str(), t[0].gene.as_str(), t[0].reverse), ("T1", "G1", true));
+        assert_eq!(t[0].exons, [(9, 30), (49, 60)]);
+        assert_eq!(t[0].len(), 32);
+        assert_eq!(t[0].blocks(15, 25), [(24, 30), (49, 53)]);
+
+        let gff = dir.path().join("a.gff3");
+        fs::write(
+            &gff,
+            "##gff-version 3\n\
+             c2\tsrc\tgene\t1\t100\t.\t+\t.\tID=g1;Name=A\n\
+             c2\tsrc\tmRNA\t1\t100\t.\t+\t.\tID=t1;Parent=g1\n\
+             c2\tsrc\tmRNA\t1\t100\t.\t+\t.\tID=t2;Parent=g1\n\
+             c2\tsrc\texon\t1\t10\t.\t+\t.\tParent=t1,t2\n\
+             c2\tsrc\texon\t41\t50\t.\t+\t.\tParent=t2\n",
+        )
+        .unwrap();
+        let t = read_annotation(&gff).unwrap();
+        assert_eq!(t.iter().map(|t| (t.id.as_str(), t.gene.as_str(), t.len())).collect::<Vec<_>>(), [("t1", "g1", 10), ("t2", "g1", 20)]);
+
+        let tpm = dir.path().join("quant.sf");
+        fs::write(&tpm, "Name\tLength\tEffectiveLength\tTPM\tNumReads\nt1\t10\t5\t12.5\t3\nt2\t20\t9\t0\t0\n").unwrap();
+        let tpm = read_tpm(&tpm).unwrap();
+        assert_eq!((tpm["t1"], tpm["t2"]), (12.5, 0.0));
+    }
+
+    #[test]
+    fn transcript_windows_are_spliced_and_weighted_by_expression() {
+        let seq: Vec<u8> = (0..400).map(|i| b"ACGT"[(i * 7 + i / 5) % 4]).collect();
+        let t = |id: &str, reverse, exons: &[(usize, usize)]| Transcript {
+            id: id.into(),
+            gene: "G".into(),
+            chrom: "c".into(),
+            reverse,
+            exons: exons.to_vec(),
+        };
+        let transcripts = [t("plus", false, &[(10, 60), (100, 150)]), t("minus", true, &[(200, 300)]), t("off", false, &[(0, 400)])];
+        // 'plus' may only be drawn from its offsets 0..30 and 40..100
+        let pieces = vec![vec![(0, 30), (40, 100)], vec![(0, 100)], vec![(0, 400)]];
+        let table = TranscriptTable::new(&transcripts, &[1.0, 1.0, 0.0], pieces);
+        assert_eq!(table.longest(), 100);
+        assert_eq!(table.pick(101, &mut StdRng::seed_from_u64(1)), None);
+
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut plus = 0;
+        let mut spliced = false;
+        for _ in 0..400 {
+            let (i, start) = table.pick(40, &mut rng).unwrap();
+            assert_ne!(i, 2, "'off' is not expressed");
+            if i == 0 {
+                plus += 1;
+                assert!((40..=60).contains(&start), "window at {} overlaps a cut", start);
+            }
+            let (bases, lift) = transcripts[i].splice(start, start + 40, |s, e| Ok(seq[s..e].to_vec())).unwrap();
+            let f = transcripts[i].fragment(bases.clone(), lift, true);
+            assert_eq!(f.reverse, i == 1, "sense strand of {}", transcripts[i].id);
+            assert_eq!(f.seq, if f.reverse { reverse_complement(&bases) } else { bases });
+            let Origin::Transcript { id, gene, blocks } = &f.origin else { panic!("not a transcript fragment") };
+            assert_eq!((id, gene.as_str()), (&transcripts[i].id, "G"));
+            let genomic: Vec<u8> = blocks.iter().flat_map(|b| seq[b.ref_pos..b.ref_pos + b.ref_len].to_vec()).collect();
+            assert_eq!(genomic.len(), 40);
+            if blocks.len() == 2 {
+                spliced = true;
+                assert_eq!((blocks[0].ref_pos + blocks[0].ref_len, blocks[1].ref_pos), (60, 100));
+                assert_eq!(f.ref_pos(blocks[1].offset), 101);
+                assert!(f.header(1).contains(&format!("blocks={}..60,101..{}", f.start + 1, f.end)));
+            }
+        }
+        assert!(plus > 100 && plus < 300, "{} of 400 from 'plus'", plus);
+        assert!(spliced);
+        // The antisense strand of a minus-strand transcript is the genomic plus strand
+        let (bases, lift) = transcripts[1].splice(0, 10, |s, e| Ok(seq[s..e].to_vec())).unwrap();
+        assert!(!transcripts[1].fragment(bases, lift, false).reverse);
+    }
+}
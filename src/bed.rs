@@ -0,0 +1,131 @@
+//! Minimal **BED** reader.
+//!
+//! Reads the first six BED columns (`chrom`, `start`, `end`, and optionally `name`, `score`, `strand`). Coordinates
+//! are **0-based, half-open** as in the BED specification. Blank lines, `#` comments and `track`/`browser` lines
+//! are skipped. Input may be compressed or `-` for stdin, like every other limpet input.
+
+use crate::seqio::open_input;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+
+/// One BED interval.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BedRecord {
+    pub chrom: String,
+    /// 0-based start
+    pub start: u64,
+    /// 0-based exclusive end
+    pub end: u64,
+    /// Column 4, if present
+    pub name: Option<String>,
+    /// Column 6 (`+` or `-`), if present
+    pub strand: Option<char>,
+}
+
+/// Parse one BED data line (`line_no` is used in error messages).
+pub fn parse_bed_line(line: &str, line_no: usize) -> Result<BedRecord> {
+    let cols: Vec<&str> = line.split('\t').map(str::trim).collect();
+    if cols.len() < 3 {
+        return Err(anyhow!("line {}: expected at least 3 tab-separated BED columns", line_no));
+    }
+    let coord = |s: &str, what: &str| -> Result<u64> {
+        s.parse().map_err(|_| anyhow!("line {}: invalid BED {} '{}'", line_no, what, s))
+    };
+    let start = coord(cols[1], "start")?;
+    let end = coord(cols[2], "end")?;
+    if end < start {
+        return Err(anyhow!("line {}: BED end {} is before start {}", line_no, end, start));
+    }
+    let name = cols.get(3).filter(|s| !s.is_empty() && **s != ".").map(|s| s.to_string());
+    let strand = match cols.get(5).copied() {
+        None | Some("") | Some(".") => None,
+        Some("+") => Some('+'),
+        Some("-") => Some('-'),
+        Some(s) => return Err(anyhow!("line {}: invalid BED strand '{}'", line_no, s)),
+    };
+    Ok(BedRecord { chrom: cols[0].to_string(), start, end, name, strand })
+}
+
+/// Read all intervals of a BED file, in file order.
+pub fn read_bed<P: AsRef<Path>>(path: P) -> Result<Vec<BedRecord>> {
+    let path = path.as_ref();
+    let mut out = Vec::new();
+    for (i, line) in open_input(path)?.lines().enumerate() {
+        let line = line?;
+        let t = line.trim();
+        if t.is_empty() || t.starts_with('#') || t.starts_with("track") || t.starts_with("browser") { continue; }
+        out.push(parse_bed_line(&line, i + 1).map_err(|e| anyhow!("{}: {}", path.display(), e))?);
+    }
+    Ok(out)
+}
+
+/// Group intervals by chromosome, each list sorted with overlapping and touching intervals merged.
+pub fn merge_by_chrom(records: &[BedRecord]) -> HashMap<String, Vec<(u64, u64)>> {
+    let mut by_chrom: HashMap<String, Vec<(u64, u64)>> = HashMap::new();
+    for r in records {
+        if r.end > r.start {
+            by_chrom.entry(r.chrom.clone()).or_default().push((r.start, r.end));
+        }
+    }
+    for v in by_chrom.values_mut() {
+        v.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(v.len());
+        for &(s, e) in v.iter() {
+            match merged.last_mut() {
+                Some(last) if s <= last.1 => last.1 = last.1.max(e),
+                _ => merged.push((s, e)),
+            }
+        }
+        *v = merged;
+    }
+    by_chrom
+}
+
+/// Remove the intervals in `remove` from `keep`; both must be sorted and merged (see [`merge_by_chrom`]).
+pub fn subtract(keep: &[(u64, u64)], remove: &[(u64, u64)]) -> Vec<(u64, u64)> {
+    let mut out = Vec::with_capacity(keep.len());
+    let mut j = 0;
+    for &(start, end) in keep {
+        let mut s = start;
+        while j < remove.len() && remove[j].1 <= s { j += 1; }
+        let mut k = j;
+        while k < remove.len() && remove[k].0 < end {
+            if remove[k].0 > s { out.push((s, remove[k].0)); }
+            s = s.max(remove[k].1);
+            k += 1;
+        }
+        if s < end { out.push((s, end)); }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bed3_and_bed6() {
+        let r = parse_bed_line("chr1\t0\t10", 1).unwrap();
+        assert_eq!((r.start, r.end, r.name, r.strand), (0, 10, None, None));
+        let r = parse_bed_line("chr2\t5\t9\tfrag1\t0\t-", 2).unwrap();
+        assert_eq!((r.name.as_deref(), r.strand), (Some("frag1"), Some('-')));
+        assert!(parse_bed_line("chr1\t9\t5", 3).is_err());
+        assert!(parse_bed_line("chr1 0 10", 4).is_err());
+    }
+
+    #[test]
+    fn merges_and_subtracts_intervals() {
+        let recs: Vec<BedRecord> = ["c\t10\t20", "c\t15\t30", "c\t30\t35", "c\t50\t60", "d\t5\t5"]
+            .iter()
+            .enumerate()
+            .map(|(i, l)| parse_bed_line(l, i + 1).unwrap())
+            .collect();
+        let merged = merge_by_chrom(&recs);
+        assert_eq!(merged["c"], [(10, 35), (50, 60)]);
+        assert!(!merged.contains_key("d"));
+        assert_eq!(subtract(&merged["c"], &[(0, 12), (20, 22), (34, 55)]), [(12, 20), (22, 34), (55, 60)]);
+        assert_eq!(subtract(&[(0, 100)], &[]), [(0, 100)]);
+    }
+}
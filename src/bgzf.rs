@@ -0,0 +1,229 @@
+//! Blocked gzip (**BGZF**) support.
+//!
+//! BGZF is the block-compressed gzip variant used by `samtools`/`htslib`: a series of independent gzip members of at
+//! most 64 KiB each, carrying their compressed size in a `BC` extra field, and terminated by a fixed empty block.
+//! Any gzip reader (including [`flate2::read::MultiGzDecoder`]) can decompress it; the block structure additionally
+//! allows random access, using a `.gzi` index ([`GziIndex`]) that maps uncompressed offsets to block starts.
+
+use anyhow::{anyhow, Context, Result};
+use flate2::read::MultiGzDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Maximum uncompressed payload per block, as used by htslib.
+pub const MAX_BLOCK_DATA: usize = 0xff00;
+
+/// Empty block that marks the end of a BGZF stream.
+pub const EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00, 0x1b, 0x00, 0x03,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Streaming BGZF writer. Call [`BgzfWriter::finish`] to write the last block and the EOF marker.
+pub struct BgzfWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    level: Compression,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    pub fn new(inner: W) -> Self {
+        BgzfWriter { inner, buf: Vec::with_capacity(MAX_BLOCK_DATA), level: Compression::default() }
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let mut enc = DeflateEncoder::new(Vec::with_capacity(self.buf.len()), self.level);
+        enc.write_all(&self.buf)?;
+        let cdata = enc.finish()?;
+        let mut crc = Crc::new();
+        crc.update(&self.buf);
+
+        // 18-byte header + deflate payload + CRC32 + ISIZE
+        let bsize = 18 + cdata.len() + 8;
+        let bsize_minus_one = u16::try_from(bsize - 1)
+            .map_err(|_| io::Error::other("BGZF block exceeds 64 KiB after compression"))?;
+        let mut header = [0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00, 0, 0];
+        header[16..18].copy_from_slice(&bsize_minus_one.to_le_bytes());
+        self.inner.write_all(&header)?;
+        self.inner.write_all(&cdata)?;
+        self.inner.write_all(&crc.sum().to_le_bytes())?;
+        self.inner.write_all(&(self.buf.len() as u32).to_le_bytes())?;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Write any buffered data and the EOF marker block, returning the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_block()?;
+        self.inner.write_all(&EOF_BLOCK)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let room = MAX_BLOCK_DATA - self.buf.len();
+        let n = room.min(data.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == MAX_BLOCK_DATA {
+            self.write_block()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        self.inner.flush()
+    }
+}
+
+/// Block index of a BGZF file, in the `.gzi` format written by `bgzip -i` and `samtools faidx`.
+///
+/// Holds `(compressed, uncompressed)` offsets of every block start after the first (which is implicitly `(0, 0)`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GziIndex {
+    blocks: Vec<(u64, u64)>,
+}
+
+impl GziIndex {
+    /// Scan the blocks of a BGZF stream.
+    pub fn build<R: Read>(rdr: R) -> Result<GziIndex> {
+        let mut rdr = BufReader::new(rdr);
+        let mut blocks = Vec::new();
+        let (mut coff, mut uoff) = (0u64, 0u64);
+        loop {
+            let mut header = [0u8; 12];
+            let got = read_full(&mut rdr, &mut header)?;
+            if got == 0 { break; }
+            if got < header.len() || header[..3] != [0x1f, 0x8b, 0x08] || header[3] & 0x04 == 0 {
+                return Err(anyhow!("Not a BGZF block at compressed offset {}", coff));
+            }
+            let xlen = u16::from_le_bytes([header[10], header[11]]) as usize;
+            let mut extra = vec![0u8; xlen];
+            rdr.read_exact(&mut extra).with_context(|| format!("Truncated BGZF block at offset {}", coff))?;
+
+            // Find the 'BC' subfield holding the total block size minus one
+            let mut bsize = None;
+            let mut i = 0usize;
+            while i + 4 <= xlen {
+                let slen = u16::from_le_bytes([extra[i + 2], extra[i + 3]]) as usize;
+                if &extra[i..i + 2] == b"BC" && slen == 2 && i + 6 <= xlen {
+                    bsize = Some(u16::from_le_bytes([extra[i + 4], extra[i + 5]]) as u64 + 1);
+                }
+                i += 4 + slen;
+            }
+            let bsize = bsize.ok_or_else(|| anyhow!("BGZF block at offset {} has no BC field", coff))?;
+            let skip = bsize
+                .checked_sub(12 + xlen as u64 + 4)
+                .ok_or_else(|| anyhow!("Invalid BGZF block size at offset {}", coff))?;
+            if io::copy(&mut (&mut rdr).take(skip), &mut io::sink())? < skip {
+                return Err(anyhow!("Truncated BGZF block at offset {}", coff));
+            }
+            let mut isize = [0u8; 4];
+            rdr.read_exact(&mut isize).with_context(|| format!("Truncated BGZF block at offset {}", coff))?;
+            let isize = u32::from_le_bytes(isize) as u64;
+
+            if coff > 0 && isize > 0 {
+                blocks.push((coff, uoff));
+            }
+            coff += bsize;
+            uoff += isize;
+        }
+        Ok(GziIndex { blocks })
+    }
+
+    /// Read a `.gzi` file.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<GziIndex> {
+        let path = path.as_ref();
+        let mut rdr = BufReader::new(File::open(path).with_context(|| format!("Failed to open {}", path.display()))?);
+        let mut word = [0u8; 8];
+        rdr.read_exact(&mut word).with_context(|| format!("Truncated index {}", path.display()))?;
+        let n = u64::from_le_bytes(word);
+        let mut blocks = Vec::new();
+        for _ in 0..n {
+            rdr.read_exact(&mut word).with_context(|| format!("Truncated index {}", path.display()))?;
+            let c = u64::from_le_bytes(word);
+            rdr.read_exact(&mut word).with_context(|| format!("Truncated index {}", path.display()))?;
+            blocks.push((c, u64::from_le_bytes(word)));
+        }
+        Ok(GziIndex { blocks })
+    }
+
+    /// Write the index in `.gzi` format.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut w = BufWriter::new(File::create(path).with_context(|| format!("Failed to create {}", path.display()))?);
+        w.write_all(&(self.blocks.len() as u64).to_le_bytes())?;
+        for &(c, u) in &self.blocks {
+            w.write_all(&c.to_le_bytes())?;
+            w.write_all(&u.to_le_bytes())?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Compressed offset of the block holding uncompressed offset `uoff`, and that block's uncompressed start.
+    pub fn locate(&self, uoff: u64) -> (u64, u64) {
+        let i = self.blocks.partition_point(|&(_, u)| u <= uoff);
+        if i == 0 { (0, 0) } else { self.blocks[i - 1] }
+    }
+
+    /// Read `len` uncompressed bytes starting at uncompressed offset `uoff`.
+    pub fn read_at<R: Read + Seek>(&self, rdr: &mut R, uoff: u64, len: u64) -> Result<Vec<u8>> {
+        let (coff, block_start) = self.locate(uoff);
+        rdr.seek(SeekFrom::Start(coff))?;
+        let mut dec = MultiGzDecoder::new(rdr);
+        let skip = uoff - block_start;
+        if io::copy(&mut (&mut dec).take(skip), &mut io::sink())? < skip {
+            return Err(anyhow!("Offset {} is past the end of the BGZF data", uoff));
+        }
+        let mut buf = Vec::with_capacity(len as usize);
+        (&mut dec).take(len).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Fill `buf` as far as possible, returning the number of bytes read (less than `buf.len()` only at EOF).
+fn read_full<R: Read>(rdr: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut got = 0usize;
+    while got < buf.len() {
+        match rdr.read(&mut buf[got..]) {
+            Ok(0) => break,
+            Ok(k) => got += k,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(got)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_decode_as_multi_member_gzip() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| b"ACGT"[(i % 7 % 4) as usize]).collect();
+        let mut w = BgzfWriter::new(Vec::new());
+        w.write_all(&data).unwrap();
+        let out = w.finish().unwrap();
+        assert!(out.ends_with(&EOF_BLOCK));
+        assert_eq!(&out[12..14], b"BC");
+
+        let mut back = Vec::new();
+        MultiGzDecoder::new(&out[..]).read_to_end(&mut back).unwrap();
+        assert_eq!(back, data);
+
+        let gzi = GziIndex::build(&out[..]).unwrap();
+        assert_eq!(gzi.blocks.len(), data.len() / MAX_BLOCK_DATA);
+        let got = gzi.read_at(&mut io::Cursor::new(&out), 150_000, 20).unwrap();
+        assert_eq!(got, &data[150_000..150_020]);
+    }
+}
@@ -0,0 +1,200 @@
+//! PCR duplicate families and UMIs.
+//!
+//! A sampled fragment is one **molecule**; PCR turns it into a **family** of reads whose size is drawn from a
+//! [`FamilySize`] distribution on `1, 2, 3, …`. Every molecule gets a random UMI, and each read of the family carries a
+//! copy of it with independent substitution errors ([`umi_with_errors`]), as a UMI-aware deduplicator would see it.
+//! [`add_duplicates`] turns sampled fragments into the shuffled reads of their families.
+//!
+//! ### Family sizes
+//! - `geometric:MEAN` — `P(k) = p (1 - p)^(k - 1)` with `p = 1 / MEAN`; `MEAN = 1` means no duplicates.
+//! - `negbin:MEAN,SIZE` — one plus a negative binomial (gamma–Poisson) count with mean `MEAN - 1` and dispersion
+//!   `SIZE`; small `SIZE` gives a long tail of very large families.
+
+use crate::fragment::Fragment;
+use anyhow::{anyhow, Result};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand_distr::{Distribution, Gamma, Poisson};
+use std::fmt;
+
+/// Distribution of the number of reads per molecule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FamilySize {
+    Geometric { mean: f64 },
+    NegativeBinomial { mean: f64, size: f64 },
+}
+
+impl FamilySize {
+    /// Parse `geometric:MEAN` or `negbin:MEAN,SIZE`.
+    pub fn parse(spec: &str) -> Result<FamilySize> {
+        let (kind, rest) = spec.split_once(':').unwrap_or((spec, ""));
+        let nums: Vec<f64> = rest
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().parse::<f64>().map_err(|_| anyhow!("invalid number '{}' in --duplicates '{}'", s, spec)))
+            .collect::<Result<_>>()?;
+        let d = match (kind.to_ascii_lowercase().as_str(), nums.as_slice()) {
+            ("geometric", &[mean]) => FamilySize::Geometric { mean },
+            ("negbin", &[mean, size]) => FamilySize::NegativeBinomial { mean, size },
+            _ => return Err(anyhow!("--duplicates must be geometric:MEAN or negbin:MEAN,SIZE (got '{}')", spec)),
+        };
+        let (mean, size) = match d {
+            FamilySize::Geometric { mean } => (mean, 1.0),
+            FamilySize::NegativeBinomial { mean, size } => (mean, size),
+        };
+        if !(mean >= 1.0 && mean.is_finite() && size > 0.0 && size.is_finite()) {
+            return Err(anyhow!("--duplicates '{}': MEAN must be >= 1 and SIZE > 0", spec));
+        }
+        Ok(d)
+    }
+
+    /// Mean family size.
+    pub fn mean(&self) -> f64 {
+        match *self {
+            FamilySize::Geometric { mean } | FamilySize::NegativeBinomial { mean, .. } => mean,
+        }
+    }
+
+    /// Draw one family size (at least 1).
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> usize {
+        match *self {
+            FamilySize::Geometric { mean } => {
+                if mean <= 1.0 { return 1; }
+                let u: f64 = 1.0 - rng.gen::<f64>();
+                1 + (u.ln() / (1.0 - 1.0 / mean).ln()).floor() as usize
+            }
+            FamilySize::NegativeBinomial { mean, size } => {
+                let lambda = Gamma::new(size, (mean - 1.0) / size).map(|g| g.sample(rng)).unwrap_or(0.0);
+                if lambda <= 0.0 { return 1; }
+                1 + Poisson::new(lambda).map(|p| p.sample(rng) as usize).unwrap_or(0)
+            }
+        }
+    }
+}
+
+impl fmt::Display for FamilySize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FamilySize::Geometric { mean } => write!(f, "geometric(mean={})", mean),
+            FamilySize::NegativeBinomial { mean, size } => write!(f, "negbin(mean={}, size={})", mean, size),
+        }
+    }
+}
+
+/// Random UMI of `len` bases.
+pub fn random_umi<R: Rng>(len: usize, rng: &mut R) -> Vec<u8> {
+    (0..len).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect()
+}
+
+/// Copy of `umi` in which each base is substituted with probability `rate`.
+pub fn umi_with_errors<R: Rng>(umi: &[u8], rate: f64, rng: &mut R) -> Vec<u8> {
+    umi.iter()
+        .map(|&b| {
+            if !rng.gen_bool(rate) { return b; }
+            let others: Vec<u8> = b"ACGT".iter().copied().filter(|&x| x != b).collect();
+            others[rng.gen_range(0..others.len())]
+        })
+        .collect()
+}
+
+/// One read of a PCR duplicate family (see [`add_duplicates`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FamilyRead {
+    /// Index of the molecule (sampled fragment) the read copies
+    pub molecule: usize,
+    /// Number of reads of the molecule
+    pub family_size: usize,
+    /// The molecule's true UMI; the read's observed UMI is in [`Fragment::umi`]
+    pub umi: String,
+}
+
+/// Copy every molecule into a family of reads with sizes from `family`, give each molecule a random UMI of
+/// `umi_length` bases and each read a copy of it with substitution errors at `umi_error_rate`. Reads are returned
+/// shuffled, with their family membership.
+pub fn add_duplicates<R: Rng>(
+    molecules: Vec<Fragment>,
+    family: &FamilySize,
+    umi_length: usize,
+    umi_error_rate: f64,
+    rng: &mut R,
+) -> (Vec<Fragment>, Vec<FamilyRead>) {
+    let mut reads: Vec<(Fragment, FamilyRead)> = Vec::new();
+    for (m, molecule) in molecules.into_iter().enumerate() {
+        let size = family.sample(rng);
+        let umi = random_umi(umi_length, rng);
+        for _ in 0..size {
+            let observed = String::from_utf8_lossy(&umi_with_errors(&umi, umi_error_rate, rng)).into_owned();
+            let info = FamilyRead { molecule: m, family_size: size, umi: String::from_utf8_lossy(&umi).into_owned() };
+            reads.push((Fragment { umi: Some(observed), ..molecule.clone() }, info));
+        }
+    }
+    reads.shuffle(rng);
+    reads.into_iter().unzip()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fragment::Origin;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashMap;
+
+    #[test]
+    fn family_sizes_and_umi_errors_follow_their_parameters() {
+        let mut rng = StdRng::seed_from_u64(8);
+        for spec in ["geometric:1", "geometric:3.5", "negbin:4,0.5", "negbin:2,10"] {
+            let d = FamilySize::parse(spec).unwrap();
+            let sizes: Vec<usize> = (0..50_000).map(|_| d.sample(&mut rng)).collect();
+            let mean = sizes.iter().sum::<usize>() as f64 / sizes.len() as f64;
+            assert!(sizes.iter().all(|&k| k >= 1));
+            assert!((mean - d.mean()).abs() < 0.05 * d.mean(), "{}: mean {}", d, mean);
+        }
+        assert!(FamilySize::parse("geometric:0.5").is_err() && FamilySize::parse("negbin:3").is_err());
+
+        let umi = random_umi(100_000, &mut rng);
+        let observed = umi_with_errors(&umi, 0.02, &mut rng);
+        let diffs = umi.iter().zip(&observed).filter(|(a, b)| a != b).count();
+        assert!((1_600..2_400).contains(&diffs), "{} substitutions", diffs);
+        assert_eq!(umi_with_errors(&umi, 0.0, &mut rng), umi);
+    }
+
+    #[test]
+    fn families_copy_their_molecule_and_share_its_umi() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let molecules: Vec<Fragment> = (0..50)
+            .map(|i| Fragment {
+                contig: "c".into(),
+                start: i * 10,
+                end: i * 10 + 8,
+                reverse: i % 2 == 1,
+                seq: random_umi(8, &mut rng),
+                circular_len: None,
+                origin: Origin::Genome,
+                umi: None,
+            })
+            .collect();
+        let family = FamilySize::parse("geometric:4").unwrap();
+        let (reads, families) = add_duplicates(molecules.clone(), &family, 10, 0.0, &mut rng);
+        assert_eq!(reads.len(), families.len());
+        assert!(reads.len() > 100);
+        // Reads are shuffled, so families are not adjacent
+        assert!(families.windows(2).any(|w| w[0].molecule > w[1].molecule));
+
+        let mut by_molecule: HashMap<usize, Vec<(&Fragment, &FamilyRead)>> = HashMap::new();
+        for (read, fam) in reads.iter().zip(&families) {
+            by_molecule.entry(fam.molecule).or_default().push((read, fam));
+        }
+        assert_eq!(by_molecule.len(), molecules.len());
+        for (m, family) in &by_molecule {
+            let molecule = &molecules[*m];
+            assert_eq!(family.len(), family[0].1.family_size);
+            for (read, fam) in family {
+                assert_eq!((read.start, read.reverse, &read.seq), (molecule.start, molecule.reverse, &molecule.seq));
+                // Without UMI errors every read carries its molecule's UMI
+                assert_eq!(read.umi.as_deref(), Some(fam.umi.as_str()));
+                assert_eq!((fam.umi.len(), &fam.umi), (10, &family[0].1.umi));
+            }
+        }
+    }
+}
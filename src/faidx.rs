@@ -0,0 +1,763 @@
+//! FASTA indexing and random access (`faidx`).
+//!
+//! Builds and reads **samtools-compatible** `.fai` indexes for plain and **BGZF**-compressed FASTA (the latter with
+//! a companion `.gzi` block index, see [`GziIndex`]). An [`IndexedFasta`] fetches any interval by seeking, so only
+//! the requested bases are read into memory. [`Reference`] picks indexed access when the file allows it and falls
+//! back to loading the whole reference otherwise (stdin, FASTQ, plain gzip, zstd, ...).
+//!
+//! `faidx` writes indexes next to the FASTA (`genome.fa.fai`, `genome.fa.gz.gzi`). Other commands use an existing,
+//! up-to-date index and otherwise build one in memory with a note on stderr, so they never write into input
+//! directories.
+//!
+//! ### Region extraction
+//! Given regions, `faidx` writes their sequences as FASTA. Regions are `name`, `name:start-end` or `name:start..end`
+//! on the command line (**1-based, inclusive**, the same convention as `seq_sample` headers), intervals of a BED
+//! file (0-based, half-open), or the `src=<contig> range=<start>..<end>` headers of a `seq_sample` (or `scramble`)
+//! output, reverse-complemented for `strand=-` fragments and wrapped around the origin for `topology=circular`
+//! fragments. Output headers are `name:start-end`, with `/rc` appended for reverse-complemented regions, followed by
+//! the BED name or original record name.
+//!
+//! ### Example
+//! ```text
+//! limpet faidx genome.fa.gz
+//! limpet faidx genome.fa.gz chr1:10001-10120 chr2:5000..5100 -o regions.fa
+//! limpet faidx genome.fa.gz --headers fragments.fa --reverse-complement
+//! ```
+
+use crate::bed::{read_bed, BedRecord};
+use crate::bgzf::GziIndex;
+use crate::seqio::{
+    create_output_with, is_stdio, open_input, read_sequences_with, reverse_complement, sniff_codec, split_header,
+    trim_end, Codec, Contig, FastaWriter, SeqMode,
+};
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Index a FASTA file (`.fai`, plus `.gzi` for BGZF), or extract regions from it.
+#[derive(Args, Debug, Clone)]
+pub struct FaidxArgs {
+    /// FASTA file, plain or BGZF-compressed (`bgzip`, or limpet's `--compress bgzf`); other inputs are loaded into
+    /// memory
+    #[arg(value_name = "FASTA")]
+    pub reference: PathBuf,
+
+    /// Regions to extract: `name`, `name:start-end` or `name:start..end` (1-based, inclusive)
+    #[arg(value_name = "REGION")]
+    pub regions: Vec<String>,
+
+    /// Extract the intervals of a BED file (0-based, half-open)
+    #[arg(long = "bed", value_name = "BED")]
+    pub bed: Option<PathBuf>,
+
+    /// Extract the regions named by `src=<contig> range=<start>..<end>` headers (e.g. a `seq_sample` output)
+    #[arg(long = "headers", value_name = "FILE")]
+    pub headers: Option<PathBuf>,
+
+    /// Reverse-complement every extracted region
+    #[arg(short = 'i', long = "reverse-complement")]
+    pub reverse_complement: bool,
+
+    /// Output FASTA for extracted regions; `-` writes stdout
+    #[arg(short = 'o', long = "output", value_name = "FASTA", default_value = "-")]
+    pub output: PathBuf,
+
+    /// Output compression (default: from the output file extension; plain for stdout)
+    #[arg(long = "compress", value_enum, value_name = "CODEC")]
+    pub compress: Option<Codec>,
+
+    /// Keep original case (soft-masking) instead of upper-casing; gap symbols such as `-` are kept either way
+    #[arg(long = "keep-case")]
+    pub keep_case: bool,
+}
+
+/// One line of a `.fai` index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaiEntry {
+    /// Sequence name (first header token)
+    pub name: String,
+    /// Number of bases
+    pub length: u64,
+    /// Uncompressed byte offset of the first base
+    pub offset: u64,
+    /// Bases per full line
+    pub line_bases: u64,
+    /// Bytes per full line, including the line terminator
+    pub line_width: u64,
+}
+
+impl FaiEntry {
+    /// Uncompressed byte offset of 0-based base `pos`.
+    fn base_offset(&self, pos: u64) -> u64 {
+        if self.line_bases == 0 {
+            return self.offset;
+        }
+        self.offset + pos / self.line_bases * self.line_width + pos % self.line_bases
+    }
+}
+
+/// Contents of a `.fai` index, in file order.
+#[derive(Debug, Clone, Default)]
+pub struct FastaIndex {
+    entries: Vec<FaiEntry>,
+    by_name: HashMap<String, usize>,
+}
+
+impl FastaIndex {
+    fn from_entries(entries: Vec<FaiEntry>) -> Result<FastaIndex> {
+        let mut by_name = HashMap::with_capacity(entries.len());
+        for (i, e) in entries.iter().enumerate() {
+            if by_name.insert(e.name.clone(), i).is_some() {
+                return Err(anyhow!("Duplicate sequence name '{}'", e.name));
+            }
+        }
+        Ok(FastaIndex { entries, by_name })
+    }
+
+    /// Index an uncompressed FASTA stream.
+    ///
+    /// Like samtools, every sequence line of a record except the last must have the same length.
+    pub fn build<R: BufRead>(mut rdr: R) -> Result<FastaIndex> {
+        let mut entries: Vec<FaiEntry> = Vec::new();
+        let mut line = Vec::new();
+        let mut pos = 0u64;
+        let mut line_no = 0u64;
+        // Set once a record has had a short (or blank) line; only a new header may follow
+        let mut ended = false;
+        loop {
+            line.clear();
+            let n = rdr.read_until(b'\n', &mut line)? as u64;
+            if n == 0 { break; }
+            line_no += 1;
+            pos += n;
+            let bases = trim_end(&line).len() as u64;
+            match line[0] {
+                b'>' => {
+                    let (name, _) = split_header(&line[1..]);
+                    entries.push(FaiEntry { name, length: 0, offset: pos, line_bases: 0, line_width: 0 });
+                    ended = false;
+                    continue;
+                }
+                b'@' if entries.is_empty() => return Err(anyhow!("FASTQ input cannot be indexed as FASTA")),
+                _ => {}
+            }
+            let Some(e) = entries.last_mut() else {
+                if bases == 0 { continue; }
+                return Err(anyhow!("line {}: expected a FASTA header ('>')", line_no));
+            };
+            if bases == 0 {
+                ended = true;
+                continue;
+            }
+            if ended {
+                return Err(anyhow!("line {}: different line length in sequence '{}'", line_no, e.name));
+            }
+            if e.line_bases == 0 {
+                e.line_bases = bases;
+                e.line_width = n;
+            } else if bases > e.line_bases || (bases == e.line_bases && n > e.line_width) {
+                return Err(anyhow!("line {}: different line length in sequence '{}'", line_no, e.name));
+            }
+            if bases < e.line_bases || n < e.line_width {
+                ended = true;
+            }
+            e.length += bases;
+        }
+        if entries.is_empty() {
+            return Err(anyhow!("No sequences found in FASTA."));
+        }
+        FastaIndex::from_entries(entries)
+    }
+
+    /// Read a `.fai` file.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<FastaIndex> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut entries = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.is_empty() { continue; }
+            let cols: Vec<&str> = line.split('\t').collect();
+            let num = |k: usize| -> Result<u64> {
+                cols.get(k)
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| anyhow!("{}:{}: malformed .fai line", path.display(), i + 1))
+            };
+            if cols.len() != 5 {
+                return Err(anyhow!("{}:{}: expected 5 columns in .fai line", path.display(), i + 1));
+            }
+            entries.push(FaiEntry {
+                name: cols[0].to_string(),
+                length: num(1)?,
+                offset: num(2)?,
+                line_bases: num(3)?,
+                line_width: num(4)?,
+            });
+        }
+        FastaIndex::from_entries(entries)
+    }
+
+    /// Write the index in `.fai` format.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut w = BufWriter::new(File::create(path).with_context(|| format!("Failed to create {}", path.display()))?);
+        for e in &self.entries {
+            writeln!(w, "{}\t{}\t{}\t{}\t{}", e.name, e.length, e.offset, e.line_bases, e.line_width)?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[FaiEntry] {
+        &self.entries
+    }
+
+    /// Position of sequence `name` in the index.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.by_name.get(name).copied()
+    }
+}
+
+/// `<path>.<ext>`, e.g. `genome.fa.gz` → `genome.fa.gz.fai`.
+fn side_path(path: &Path, ext: &str) -> PathBuf {
+    let mut s: OsString = path.as_os_str().to_owned();
+    s.push(".");
+    s.push(ext);
+    PathBuf::from(s)
+}
+
+/// Path of the `.fai` index for `path`.
+pub fn fai_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    side_path(path.as_ref(), "fai")
+}
+
+/// Path of the `.gzi` block index for `path`.
+pub fn gzi_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    side_path(path.as_ref(), "gzi")
+}
+
+/// True if `index` exists and is not older than `path`.
+fn is_fresh(index: &Path, path: &Path) -> bool {
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    match (modified(index), modified(path)) {
+        (Some(i), Some(p)) => i >= p,
+        _ => false,
+    }
+}
+
+/// Check that `path` is a file limpet can index, returning its codec.
+fn indexable_codec(path: &Path) -> Result<Codec> {
+    if is_stdio(path) {
+        return Err(anyhow!("stdin cannot be indexed"));
+    }
+    match sniff_codec(path)? {
+        c @ (Codec::None | Codec::Bgzf) => Ok(c),
+        Codec::Gzip => Err(anyhow!(
+            "{} is gzip but not BGZF; recompress it with `bgzip` (or limpet's `--compress bgzf`) to index it",
+            path.display()
+        )),
+        other => Err(anyhow!("{} is {:?}-compressed; only plain and BGZF FASTA can be indexed", path.display(), other)),
+    }
+}
+
+/// Build the `.fai` (and, for BGZF, `.gzi`) index of a FASTA file without writing it.
+pub fn build_index<P: AsRef<Path>>(path: P) -> Result<(FastaIndex, Option<GziIndex>)> {
+    let path = path.as_ref();
+    let gzi = match indexable_codec(path)? {
+        Codec::Bgzf => Some(GziIndex::build(File::open(path)?).with_context(|| format!("Failed to index {}", path.display()))?),
+        _ => None,
+    };
+    let fai = FastaIndex::build(open_input(path)?).with_context(|| format!("Failed to index {}", path.display()))?;
+    Ok((fai, gzi))
+}
+
+/// Build the index of a FASTA file and write it next to the file.
+pub fn write_index<P: AsRef<Path>>(path: P) -> Result<FastaIndex> {
+    let path = path.as_ref();
+    let (fai, gzi) = build_index(path)?;
+    fai.write(fai_path(path))?;
+    if let Some(gzi) = gzi {
+        gzi.write(gzi_path(path))?;
+    }
+    Ok(fai)
+}
+
+/// A FASTA file opened for random access through its index.
+pub struct IndexedFasta {
+    file: File,
+    index: FastaIndex,
+    /// Block index; present for BGZF files
+    gzi: Option<GziIndex>,
+    source: String,
+}
+
+impl IndexedFasta {
+    /// Open a FASTA file using its existing, up-to-date index.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let codec = indexable_codec(path)?;
+        let fai = fai_path(path);
+        if !is_fresh(&fai, path) {
+            return Err(anyhow!("No up-to-date index {}; run `limpet faidx {}`", fai.display(), path.display()));
+        }
+        let gzi = match codec {
+            Codec::Bgzf => {
+                let g = gzi_path(path);
+                if !is_fresh(&g, path) {
+                    return Err(anyhow!("No up-to-date index {}; run `limpet faidx {}`", g.display(), path.display()));
+                }
+                Some(GziIndex::read(g)?)
+            }
+            _ => None,
+        };
+        IndexedFasta::with_index(path, FastaIndex::read(fai)?, gzi)
+    }
+
+    /// Open a FASTA file, building its index in memory if it is missing or stale.
+    ///
+    /// The built index is not saved (use [`write_index`], i.e. `limpet faidx`, for that); a note on stderr says so.
+    pub fn open_or_build<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if let Ok(f) = IndexedFasta::open(path) {
+            return Ok(f);
+        }
+        eprintln!(
+            "Note: no up-to-date index for {}; indexing it in memory (run `limpet faidx {}` to save one)",
+            path.display(),
+            path.display()
+        );
+        let (fai, gzi) = build_index(path)?;
+        IndexedFasta::with_index(path, fai, gzi)
+    }
+
+    fn with_index(path: &Path, index: FastaIndex, gzi: Option<GziIndex>) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("Failed to open input: {}", path.display()))?;
+        Ok(IndexedFasta { file, index, gzi, source: path.display().to_string() })
+    }
+
+    pub fn index(&self) -> &FastaIndex {
+        &self.index
+    }
+
+    /// Read `len` raw bytes at uncompressed file offset `offset`.
+    fn read_raw(&mut self, offset: u64, len: u64) -> Result<Vec<u8>> {
+        match &self.gzi {
+            Some(gzi) => gzi.read_at(&mut self.file, offset, len),
+            None => {
+                self.file.seek(SeekFrom::Start(offset))?;
+                let mut buf = Vec::with_capacity(len as usize);
+                (&mut self.file).take(len).read_to_end(&mut buf)?;
+                Ok(buf)
+            }
+        }
+    }
+
+    /// Header line of the `i`-th sequence, without the leading `>`.
+    ///
+    /// The `.fai` index only keeps names, so the line is read from the file, just before the sequence.
+    pub fn header_at(&mut self, i: usize) -> Result<String> {
+        let entries = &self.index.entries;
+        let e = entries.get(i).ok_or_else(|| anyhow!("No sequence #{} in {}", i, self.source))?;
+        let from = match i.checked_sub(1).map(|p| &entries[p]) {
+            None => 0,
+            Some(p) if p.length == 0 => p.offset,
+            Some(p) => p.base_offset(p.length - 1) + 1,
+        };
+        let raw = self.read_raw(from, e.offset - from)?;
+        let text = String::from_utf8_lossy(&raw);
+        match text.lines().map(str::trim).rfind(|l| l.starts_with('>')) {
+            Some(l) => Ok(l[1..].to_string()),
+            None => {
+                let name = &self.index.entries[i].name;
+                Err(anyhow!("{}: no header line for '{}'; rebuild the index with `limpet faidx`", self.source, name))
+            }
+        }
+    }
+
+    /// Fetch bases `[start, end)` (0-based) of the `i`-th sequence, exactly as stored.
+    pub fn fetch_at(&mut self, i: usize, start: u64, end: u64) -> Result<Vec<u8>> {
+        let e = self.index.entries.get(i).ok_or_else(|| anyhow!("No sequence #{} in {}", i, self.source))?;
+        if start > end || end > e.length {
+            return Err(anyhow!("Interval {}..{} is outside '{}' (length {})", start, end, e.name, e.length));
+        }
+        if start == end {
+            return Ok(Vec::new());
+        }
+        let first = e.base_offset(start);
+        let span = e.base_offset(end - 1) + 1 - first;
+        let mut raw = self.read_raw(first, span)?;
+        raw.retain(|&b| b != b'\n' && b != b'\r');
+        if raw.len() as u64 != end - start {
+            let name = &self.index.entries[i].name;
+            return Err(anyhow!("{}: index does not match the file for '{}'; rebuild it with `limpet faidx`", self.source, name));
+        }
+        Ok(raw)
+    }
+
+    /// Fetch bases `[start, end)` (0-based) of sequence `name`.
+    pub fn fetch(&mut self, name: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+        let i = self.index.position(name).ok_or_else(|| anyhow!("Sequence '{}' not found in {}", name, self.source))?;
+        self.fetch_at(i, start, end)
+    }
+}
+
+/// A reference genome, either indexed on disk or loaded into memory.
+///
+/// Both kinds give the same bases and coordinates for the same file: every non-whitespace byte is a position, as in
+/// the `.fai` index, and [`SeqMode::Normalize`] only upper-cases (gap symbols such as `-` and `*` are kept).
+pub enum Reference {
+    /// Indexed FASTA; sequences are read on demand and normalised with the given mode
+    Indexed(IndexedFasta, SeqMode),
+    /// Every sequence held in memory
+    Loaded(Vec<Contig>),
+}
+
+impl Reference {
+    /// Open a reference for interval access.
+    ///
+    /// Plain and BGZF FASTA files are indexed (in memory if no index was saved) and read on demand; anything else is
+    /// loaded into memory. In [`SeqMode::Normalize`] bases are upper-cased.
+    pub fn open<P: AsRef<Path>>(path: P, mode: SeqMode) -> Result<Self> {
+        let path = path.as_ref();
+        if indexable_codec(path).is_ok() {
+            match IndexedFasta::open_or_build(path) {
+                Ok(f) => return Ok(Reference::Indexed(f, mode)),
+                Err(e) => eprintln!("Note: loading {} into memory ({:#})", path.display(), e),
+            }
+        }
+        // Read like the indexed path: keep every symbol, so coordinates do not depend on whether there is an index
+        let mut contigs = read_sequences_with(path, SeqMode::Preserve)?;
+        if mode == SeqMode::Normalize {
+            for c in &mut contigs {
+                c.seq.make_ascii_uppercase();
+            }
+        }
+        Ok(Reference::Loaded(contigs))
+    }
+
+    /// Number of sequences.
+    pub fn len(&self) -> usize {
+        match self {
+            Reference::Indexed(f, _) => f.index.entries.len(),
+            Reference::Loaded(c) => c.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Name of the `i`-th sequence.
+    pub fn name(&self, i: usize) -> &str {
+        match self {
+            Reference::Indexed(f, _) => &f.index.entries[i].name,
+            Reference::Loaded(c) => &c[i].name,
+        }
+    }
+
+    /// Length of the `i`-th sequence.
+    pub fn seq_len(&self, i: usize) -> usize {
+        match self {
+            Reference::Indexed(f, _) => f.index.entries[i].length as usize,
+            Reference::Loaded(c) => c[i].seq.len(),
+        }
+    }
+
+    /// Position of sequence `name`.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        match self {
+            Reference::Indexed(f, _) => f.index.position(name),
+            Reference::Loaded(c) => c.iter().position(|r| r.name == name),
+        }
+    }
+
+    /// Full header line of the `i`-th sequence, without the leading `>`.
+    pub fn header(&mut self, i: usize) -> Result<String> {
+        match self {
+            Reference::Indexed(f, _) => f.header_at(i),
+            Reference::Loaded(c) => Ok(c[i].header.clone()),
+        }
+    }
+
+    /// Bases `[start, end)` (0-based) of the `i`-th sequence.
+    pub fn fetch(&mut self, i: usize, start: usize, end: usize) -> Result<Vec<u8>> {
+        match self {
+            Reference::Indexed(f, mode) => {
+                let mut seq = f.fetch_at(i, start as u64, end as u64)?;
+                if *mode == SeqMode::Normalize {
+                    seq.make_ascii_uppercase();
+                }
+                Ok(seq)
+            }
+            Reference::Loaded(c) => {
+                let r = &c[i];
+                if start > end || end > r.seq.len() {
+                    return Err(anyhow!("Interval {}..{} is outside '{}' (length {})", start, end, r.name, r.seq.len()));
+                }
+                Ok(r.seq[start..end].to_vec())
+            }
+        }
+    }
+}
+
+/// A region to extract, in 0-based half-open coordinates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// Sequence name
+    pub name: String,
+    /// 0-based start
+    pub start: u64,
+    /// 0-based exclusive end; `None` means the end of the sequence
+    pub end: Option<u64>,
+    /// Reverse-complement the extracted bases
+    pub reverse: bool,
+    /// Extra text for the output header (BED name, or the name of the record a header came from)
+    pub label: Option<String>,
+    /// The sequence is circular: `end` may exceed its length, wrapping around the origin
+    pub circular: bool,
+}
+
+impl Region {
+    /// The whole of sequence `name`.
+    pub fn whole(name: &str) -> Region {
+        Region { name: name.to_string(), start: 0, end: None, reverse: false, label: None, circular: false }
+    }
+
+    /// Parse `name`, `name:start`, `name:start-end` or `name:start..end` (1-based, inclusive; `,` separators allowed).
+    pub fn parse(spec: &str) -> Result<Region> {
+        let Some((name, range)) = spec.rsplit_once(':') else { return Ok(Region::whole(spec)) };
+        if name.is_empty() {
+            return Err(anyhow!("Invalid region '{}': missing sequence name", spec));
+        }
+        let range = range.replace(',', "");
+        let (a, b) = match range.split_once("..").or_else(|| range.split_once('-')) {
+            Some((a, b)) => (a, Some(b)),
+            None => (range.as_str(), None),
+        };
+        let pos = |s: &str| -> Result<u64> { s.trim().parse().map_err(|_| anyhow!("Invalid region '{}'", spec)) };
+        let start = pos(a)?;
+        if start == 0 {
+            return Err(anyhow!("Invalid region '{}': coordinates are 1-based", spec));
+        }
+        let end = match b {
+            Some(b) if !b.is_empty() => Some(pos(b)?),
+            _ => None,
+        };
+        if end.is_some_and(|e| e < start) {
+            return Err(anyhow!("Invalid region '{}': end is before start", spec));
+        }
+        Ok(Region { name: name.to_string(), start: start - 1, end, reverse: false, label: None, circular: false })
+    }
+
+    /// Region of a BED interval, labelled with its name column.
+    pub fn from_bed(rec: &BedRecord) -> Region {
+        Region {
+            name: rec.chrom.clone(),
+            start: rec.start,
+            end: Some(rec.end),
+            reverse: false,
+            label: rec.name.clone(),
+            circular: false,
+        }
+    }
+
+    /// Region described by a header with `src=<contig>` and `range=<start>..<end>` tokens, as written by
+    /// `seq_sample`; `None` if the header has no such tokens. The region is labelled with the header's first token,
+    /// and reverse-complemented if a `strand=-` token follows `range=`. A `topology=circular` token marks a region
+    /// that may run past the end of its (circular) sequence.
+    ///
+    /// When a header carries several `src=` tokens (e.g. after `scramble`), the one closest before `range=` is used.
+    pub fn from_header(header: &str) -> Option<Result<Region>> {
+        let tokens: Vec<&str> = header.trim_start_matches(['>', '@']).split_whitespace().collect();
+        let r = tokens.iter().rposition(|t| t.starts_with("range="))?;
+        let src = tokens[..r].iter().rev().find_map(|t| t.strip_prefix("src="))?;
+        let spec = format!("{}:{}", src, &tokens[r]["range=".len()..]);
+        let reverse = tokens[r..].contains(&"strand=-");
+        let circular = tokens[r..].contains(&"topology=circular");
+        Some(Region::parse(&spec).map(|mut reg| {
+            reg.label = tokens.first().map(|t| t.to_string());
+            reg.reverse = reverse;
+            reg.circular = circular;
+            reg
+        }))
+    }
+}
+
+/// Read every `src=... range=...` header of a file (FASTA, or plain text with one header per line).
+pub fn read_header_regions<P: AsRef<Path>>(path: P) -> Result<Vec<Region>> {
+    let path = path.as_ref();
+    let mut out = Vec::new();
+    for line in open_input(path)?.lines() {
+        if let Some(reg) = Region::from_header(&line?) {
+            out.push(reg.with_context(|| format!("Bad header region in {}", path.display()))?);
+        }
+    }
+    if out.is_empty() {
+        return Err(anyhow!("No `src=<contig> range=<start>..<end>` headers found in {}", path.display()));
+    }
+    Ok(out)
+}
+
+/// Extract `region` from `reference`, returning its FASTA header and bases.
+pub fn extract_region(reference: &mut Reference, region: &Region) -> Result<(String, Vec<u8>)> {
+    let i = reference.position(&region.name).ok_or_else(|| anyhow!("Sequence '{}' not found in reference", region.name))?;
+    let len = reference.seq_len(i) as u64;
+    let end = region.end.unwrap_or(len);
+    let wraps = region.circular && end > len && region.start < len && end - len <= region.start;
+    if region.start > len || (end > len && !wraps) {
+        return Err(anyhow!(
+            "Region {}:{}-{} is outside '{}' (length {})",
+            region.name,
+            region.start + 1,
+            end,
+            region.name,
+            len
+        ));
+    }
+    let mut seq = reference.fetch(i, region.start as usize, end.min(len) as usize)?;
+    if wraps {
+        seq.extend(reference.fetch(i, 0, (end - len) as usize)?);
+    }
+    let mut header = if region.start == 0 && region.end.is_none() {
+        region.name.clone()
+    } else {
+        format!("{}:{}-{}", region.name, region.start + 1, end)
+    };
+    if region.reverse {
+        seq = reverse_complement(&seq);
+        header.push_str("/rc");
+    }
+    if let Some(label) = &region.label {
+        header.push(' ');
+        header.push_str(label);
+    }
+    Ok((header, seq))
+}
+
+/// Execute the `faidx` subcommand.
+/// Without regions, builds and writes the index files; otherwise writes the regions' sequences as FASTA.
+pub fn run(args: FaidxArgs) -> Result<()> {
+    if args.regions.is_empty() && args.bed.is_none() && args.headers.is_none() {
+        let fai = write_index(&args.reference)?;
+        let bases: u64 = fai.entries().iter().map(|e| e.length).sum();
+        eprintln!(
+            "Indexed {} sequences ({} bases) into {}",
+            fai.entries().len(),
+            bases,
+            fai_path(&args.reference).display()
+        );
+        return Ok(());
+    }
+
+    let mode = if args.keep_case { SeqMode::Preserve } else { SeqMode::Normalize };
+    let mut reference = Reference::open(&args.reference, mode)?;
+
+    let mut regions: Vec<Region> = Vec::new();
+    for spec in &args.regions {
+        // A name containing ':' (e.g. HLA alleles) wins over a region parse, as in samtools
+        regions.push(if reference.position(spec).is_some() { Region::whole(spec) } else { Region::parse(spec)? });
+    }
+    if let Some(bed) = &args.bed {
+        regions.extend(read_bed(bed)?.iter().map(Region::from_bed));
+    }
+    if let Some(headers) = &args.headers {
+        regions.extend(read_header_regions(headers)?);
+    }
+    if args.reverse_complement {
+        for r in &mut regions {
+            r.reverse = !r.reverse;
+        }
+    }
+
+    let mut writer = FastaWriter::new(create_output_with(&args.output, args.compress)?, 80);
+    for region in &regions {
+        let (header, seq) = extract_region(&mut reference, region)?;
+        writer.write_record(&header, &seq)?;
+    }
+    writer.finish()?.close()?;
+    eprintln!("Extracted {} regions to {}", regions.len(), args.output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seqio::create_output_with;
+    use std::io::Cursor;
+    use tempfile::tempdir;
+
+    #[test]
+    fn fetches_across_lines_from_plain_and_bgzf() {
+        let fa = ">chr1 first\nACGTA\nCGTAC\nGT\n>chr2\nTTTT\nGG\n";
+        let fai = FastaIndex::build(Cursor::new(fa)).unwrap();
+        assert_eq!(fai.entries()[0], FaiEntry { name: "chr1".into(), length: 12, offset: 12, line_bases: 5, line_width: 6 });
+        assert_eq!((fai.entries()[1].offset, fai.entries()[1].length), (33, 6));
+        assert!(FastaIndex::build(Cursor::new(">x\nACG\nACGT\n")).is_err());
+
+        let dir = tempdir().unwrap();
+        for name in ["ref.fa", "ref.fa.bgz"] {
+            let path = dir.path().join(name);
+            let mut out = create_output_with(&path, None).unwrap();
+            out.write_all(fa.as_bytes()).unwrap();
+            out.close().unwrap();
+
+            let mut f = IndexedFasta::open_or_build(&path).unwrap();
+            assert_eq!(f.fetch("chr1", 3, 11).unwrap(), b"TACGTACG");
+            assert_eq!(f.fetch("chr2", 0, 6).unwrap(), b"TTTTGG");
+            assert!(f.fetch("chr2", 2, 7).is_err());
+            // Only `faidx` (write_index) leaves files next to the input
+            assert!(!fai_path(&path).exists() && IndexedFasta::open(&path).is_err());
+            write_index(&path).unwrap();
+            assert!(IndexedFasta::open(&path).is_ok());
+        }
+        assert!(gzi_path(dir.path().join("ref.fa.bgz")).exists());
+    }
+
+    #[test]
+    fn indexed_and_loaded_references_agree() {
+        let fa = ">c gapped\nACg-t\n*aNNc\nTT\n";
+        let dir = tempdir().unwrap();
+        let (plain, gzip) = (dir.path().join("ref.fa"), dir.path().join("ref.fa.gz"));
+        for (path, codec) in [(&plain, Codec::None), (&gzip, Codec::Gzip)] {
+            let mut out = create_output_with(path, Some(codec)).unwrap();
+            out.write_all(fa.as_bytes()).unwrap();
+            out.close().unwrap();
+        }
+        for mode in [SeqMode::Normalize, SeqMode::Preserve] {
+            let mut indexed = Reference::open(&plain, mode).unwrap();
+            let mut loaded = Reference::open(&gzip, mode).unwrap();
+            assert!(matches!((&indexed, &loaded), (Reference::Indexed(..), Reference::Loaded(_))));
+            assert_eq!((indexed.seq_len(0), loaded.seq_len(0)), (12, 12));
+            for (start, end) in [(0, 12), (2, 7), (9, 12)] {
+                assert_eq!(indexed.fetch(0, start, end).unwrap(), loaded.fetch(0, start, end).unwrap(), "{:?}", mode);
+            }
+            let expected: &[u8] = if mode == SeqMode::Normalize { b"ACG-T*ANNCTT" } else { b"ACg-t*aNNcTT" };
+            assert_eq!(loaded.fetch(0, 0, 12).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn regions_use_one_based_inclusive_coordinates() {
+        assert_eq!(Region::parse("chr1:2-4").unwrap(), Region { end: Some(4), start: 1, ..Region::whole("chr1") });
+        assert_eq!(Region::parse("chr1:1,001..1,010").unwrap().start, 1000);
+        assert!(Region::parse("chr1:0-4").is_err());
+        assert!(Region::parse("chr1:5-4").is_err());
+
+        let from = "scramble_00001 src=seq000001 file=f.fa | seq000001 src=chr1 range=2..4 len=3";
+        let reg = Region::from_header(from).unwrap().unwrap();
+        assert_eq!((reg.name.as_str(), reg.start, reg.end, reg.label.as_deref()), ("chr1", 1, Some(4), Some("scramble_00001")));
+        assert!(Region::from_header(">seq1 no coordinates").is_none());
+        assert!(Region::from_header(">seq2 src=chr1 range=2..4 len=3 strand=-").unwrap().unwrap().reverse);
+
+        let contigs = vec![Contig { name: "chr1".into(), header: "chr1".into(), seq: b"ACGTTG".to_vec(), qual: None }];
+        let mut reference = Reference::Loaded(contigs);
+        let (h, seq) = extract_region(&mut reference, &reg).unwrap();
+        assert_eq!((h.as_str(), seq.as_slice()), ("chr1:2-4 scramble_00001", &b"CGT"[..]));
+        let rc = Region { reverse: true, label: None, ..reg };
+        assert_eq!(extract_region(&mut reference, &rc).unwrap(), ("chr1:2-4/rc".to_string(), b"ACG".to_vec()));
+        assert!(extract_region(&mut reference, &Region::parse("chr1:5-7").unwrap()).is_err());
+    }
+}
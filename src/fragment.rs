@@ -0,0 +1,296 @@
+//! Sampled fragments and their output as simulated reads.
+//!
+//! A [`Fragment`] is a stretch of a reference with its provenance: contig, coordinates and strand, plus an [`Origin`]
+//! for what the source adds (haplotype and variants, transcript and exon blocks, or primer pair). Its header fields
+//! (`src=`, `range=`, `strand=`, ...) are shared by `seq_sample`, `amplicon` and `faidx --headers`.
+//! The crate-internal `write_reads` and `write_pairs` turn fragments into single-end or paired FASTQ reads with an
+//! error model, optionally listing every simulated error in an `--errors-out` TSV.
+
+use crate::seqio::{create_output, create_output_with, Codec, FastqWriter, Output};
+use crate::simulate::{read_pair, ErrorModel, PairNaming, SimulatedRead};
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use std::io::Write;
+use std::path::Path;
+
+/// Where a run of fragment bases comes from on the reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiftBlock {
+    /// Offset of the first base in the fragment
+    pub offset: usize,
+    /// 0-based reference position of the first base
+    pub ref_pos: usize,
+    /// Reference bases spanned; bases past `ref_len - 1` (inserted bases) map to the last of them
+    pub ref_len: usize,
+}
+
+/// Which source a [`Fragment`] was taken from, with what that source adds to its provenance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// The reference itself; bases map 1:1 from `start`
+    Genome,
+    /// A VCF haplotype
+    Haplotype {
+        /// Haplotype number (1-based)
+        index: usize,
+        /// Labels of the variants the fragment carries
+        variants: Vec<String>,
+        /// Reference origin of the fragment's bases (forward strand)
+        lift: Vec<LiftBlock>,
+    },
+    /// A spliced transcript of an annotation
+    Transcript {
+        id: String,
+        gene: String,
+        /// Exon blocks of the fragment (forward strand)
+        blocks: Vec<LiftBlock>,
+    },
+    /// An in-silico PCR product
+    Amplicon {
+        /// Name of the primer pair
+        primer: String,
+        forward_mismatches: usize,
+        reverse_mismatches: usize,
+    },
+}
+
+impl Origin {
+    /// Where the fragment's bases come from on the reference; empty when they map 1:1 from `start`.
+    pub fn lift(&self) -> &[LiftBlock] {
+        match self {
+            Origin::Haplotype { lift, .. } => lift,
+            Origin::Transcript { blocks, .. } => blocks,
+            Origin::Genome | Origin::Amplicon { .. } => &[],
+        }
+    }
+}
+
+/// A sampled fragment with its reference provenance.
+#[derive(Debug, Clone)]
+pub struct Fragment {
+    /// Accession of the source contig
+    pub contig: String,
+    /// 0-based start on the contig
+    pub start: usize,
+    /// 0-based exclusive end on the contig; past `circular_len` for a fragment spanning the origin
+    pub end: usize,
+    /// Taken from the minus strand (`seq` is reverse-complemented)
+    pub reverse: bool,
+    /// Fragment sequence, 5'→3' on its strand
+    pub seq: Vec<u8>,
+    /// Length of the source contig if it is circular
+    pub circular_len: Option<usize>,
+    /// The source the fragment was taken from
+    pub origin: Origin,
+    /// UMI as read, when the fragment is a read of a PCR duplicate family
+    pub umi: Option<String>,
+}
+
+impl Fragment {
+    /// FASTA header for the `id`-th fragment (1-based), using 1-based inclusive coordinates.
+    pub fn header(&self, id: usize) -> String {
+        format!("{} {}", fragment_name(id), self.describe())
+    }
+
+    /// Provenance part of the header: `src=<contig> range=<start>..<end> len=<len> strand=<+|->`, followed by
+    /// `topology=circular` for fragments of circular contigs (whose `range=` end may exceed the contig length) and
+    /// `hap=<n> vars=<labels>` for fragments of VCF haplotypes and `transcript=<id> gene=<id> blocks=<s>..<e>,...`
+    /// for transcript fragments; amplicons add `amplicon=<primer> mismatches=<forward>,<reverse>` and reads of
+    /// duplicate families `umi=<bases>`.
+    pub fn describe(&self) -> String {
+        let mut d = format!(
+            "src={} range={}..{} len={} strand={}",
+            self.contig,
+            self.start + 1,
+            self.end,
+            self.seq.len(),
+            self.strand()
+        );
+        if self.circular_len.is_some() {
+            d.push_str(" topology=circular");
+        }
+        match &self.origin {
+            Origin::Genome => {}
+            Origin::Haplotype { index, variants, .. } => {
+                d.push_str(&format!(" hap={}", index));
+                if !variants.is_empty() {
+                    d.push_str(&format!(" vars={}", variants.join(",")));
+                }
+            }
+            Origin::Transcript { id, gene, blocks } => {
+                let blocks: Vec<String> = blocks.iter().map(|b| format!("{}..{}", b.ref_pos + 1, b.ref_pos + b.ref_len)).collect();
+                d.push_str(&format!(" transcript={} gene={} blocks={}", id, gene, blocks.join(",")));
+            }
+            Origin::Amplicon { primer, forward_mismatches, reverse_mismatches } => {
+                d.push_str(&format!(" amplicon={} mismatches={},{}", primer, forward_mismatches, reverse_mismatches));
+            }
+        }
+        if let Some(umi) = &self.umi {
+            d.push_str(&format!(" umi={}", umi));
+        }
+        d
+    }
+
+    /// 1-based reference position of the base at forward-strand offset `off`, counting past the origin of a
+    /// circular contig like `end` does. Inserted bases map to their anchor.
+    pub fn ref_pos(&self, off: usize) -> usize {
+        let lift = self.origin.lift();
+        if lift.is_empty() {
+            return self.start + off + 1;
+        }
+        let b = &lift[lift.partition_point(|b| b.offset <= off) - 1];
+        let pos = b.ref_pos + (off - b.offset).min(b.ref_len - 1) + 1;
+        match self.circular_len {
+            Some(len) if pos <= self.start => pos + len,
+            _ => pos,
+        }
+    }
+
+    /// Map a 1-based position that may run past the origin of a circular contig back onto the contig.
+    pub fn wrap(&self, pos: usize) -> usize {
+        match self.circular_len {
+            Some(len) if pos > len => pos - len,
+            _ => pos,
+        }
+    }
+
+    /// Strand symbol, `+` or `-`.
+    pub fn strand(&self) -> char {
+        if self.reverse { '-' } else { '+' }
+    }
+}
+
+/// Name of the `id`-th fragment (1-based): `seq000001`.
+pub fn fragment_name(id: usize) -> String {
+    format!("seq{:06}", id)
+}
+
+/// Destinations and naming of simulated reads, shared by `seq_sample` and [`crate::amplicon`].
+pub(crate) struct ReadOutput<'a> {
+    pub output: &'a Path,
+    /// R2 output in paired mode
+    pub output2: Option<&'a Path>,
+    pub compress: Option<Codec>,
+    pub errors_out: Option<&'a Path>,
+    /// Bases read from each end in paired mode
+    pub read_length: Option<usize>,
+    pub read_names: PairNaming,
+}
+
+/// Open the `--errors-out` TSV and write its header line.
+fn create_errors_out(path: Option<&Path>) -> Result<Option<Output>> {
+    match path {
+        Some(p) => {
+            let mut w = create_output(p)?;
+            writeln!(w, "#read\tcontig\tref_pos\tread_pos\ttype\tref\talt")?;
+            Ok(Some(w))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Write the errors of `read` as TSV lines; `reverse` is true if the read's template runs along the minus strand.
+/// Reference positions are 1-based forward-strand coordinates; bases are given on the read's strand.
+fn write_errors<W: Write>(w: &mut W, id: &str, f: &Fragment, read: &SimulatedRead, reverse: bool) -> Result<()> {
+    let base = |b: Option<u8>| b.map(|b| (b as char).to_string()).unwrap_or_else(|| ".".to_string());
+    for e in &read.errors {
+        let off = if reverse { f.seq.len() - 1 - e.template_pos } else { e.template_pos };
+        let ref_pos = f.wrap(f.ref_pos(off));
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            id,
+            f.contig,
+            ref_pos,
+            e.read_pos + 1,
+            e.kind.code(),
+            base(e.template_base),
+            base(e.read_base)
+        )?;
+    }
+    Ok(())
+}
+
+/// ` subs=<n> ins=<n> dels=<n>` header suffix for a simulated read.
+fn error_counts(read: &SimulatedRead) -> String {
+    let (subs, ins, dels) = read.counts();
+    format!(" subs={} ins={} dels={}", subs, ins, dels)
+}
+
+/// Single-end mode with an error model: one FASTQ read per fragment.
+pub(crate) fn write_reads<R: Rng>(out: &ReadOutput, fragments: &[Fragment], model: &ErrorModel, rng: &mut R) -> Result<()> {
+    let mut writer = FastqWriter::new(create_output_with(out.output, out.compress)?);
+    let mut errors_out = create_errors_out(out.errors_out)?;
+    for (i, f) in fragments.iter().enumerate() {
+        let read = model.simulate(&f.seq, rng);
+        let header = format!("{}{}", f.header(i + 1), error_counts(&read));
+        writer.write_record(&header, &read.seq, &read.qual)?;
+        if let Some(w) = errors_out.as_mut() {
+            write_errors(w, &fragment_name(i + 1), f, &read, f.reverse)?;
+        }
+    }
+    writer.finish()?.close()?;
+    if let Some(w) = errors_out {
+        w.close()?;
+    }
+    eprintln!(
+        "Wrote {} {} reads (mean error rate {}) to {}",
+        fragments.len(),
+        model.profile.name(),
+        model.rate,
+        out.output.display()
+    );
+    Ok(())
+}
+
+/// Paired-end mode: R1 and R2 FASTQ reads from both ends of every fragment. `inserts` describes the insert sizes in
+/// the summary line.
+pub(crate) fn write_pairs<R: Rng>(
+    out: &ReadOutput,
+    inserts: &str,
+    fragments: &[Fragment],
+    model: Option<&ErrorModel>,
+    rng: &mut R,
+) -> Result<()> {
+    let read_len = out.read_length.ok_or_else(|| anyhow!("--paired requires --read-length"))?;
+    let output2 = out.output2.ok_or_else(|| anyhow!("--paired requires --output2"))?;
+    let mut w1 = FastqWriter::new(create_output_with(out.output, out.compress)?);
+    let mut w2 = FastqWriter::new(create_output_with(output2, out.compress)?);
+    let mut errors_out = create_errors_out(out.errors_out)?;
+
+    for (i, f) in fragments.iter().enumerate() {
+        let (r1, r2) = read_pair(&f.seq, read_len, model, rng);
+        let name = fragment_name(i + 1);
+        // R1 reads from the fragment's 5' end: the forward start, or the forward end for minus-strand fragments
+        // Reads are clipped to inserts shorter than the read length
+        let (len, read_len) = (f.seq.len(), read_len.min(f.seq.len()));
+        let (head, tail) = ((f.ref_pos(0), f.ref_pos(read_len - 1)), (f.ref_pos(len - read_len), f.ref_pos(len - 1)));
+        let ((r1s, r1e), (r2s, r2e)) = if f.reverse { (tail, head) } else { (head, tail) };
+        let info = format!("{} r1={}..{} r2={}..{}", f.describe(), r1s, r1e, r2s, r2e);
+        for (mate, read, w) in [(1u8, &r1, &mut w1), (2u8, &r2, &mut w2)] {
+            let mut header = format!("{} {}", out.read_names.read_name(&name, mate), info);
+            if model.is_some() {
+                header.push_str(&error_counts(read));
+            }
+            w.write_record(&header, &read.seq, &read.qual)?;
+        }
+        if let Some(w) = errors_out.as_mut() {
+            write_errors(w, &out.read_names.read_name(&name, 1), f, &r1, f.reverse)?;
+            write_errors(w, &out.read_names.read_name(&name, 2), f, &r2, !f.reverse)?;
+        }
+    }
+    w1.finish()?.close()?;
+    w2.finish()?.close()?;
+    if let Some(w) = errors_out {
+        w.close()?;
+    }
+    eprintln!(
+        "Wrote {} read pairs ({} bp, inserts {}) to {} and {}",
+        fragments.len(),
+        read_len,
+        inserts,
+        out.output.display(),
+        output2.display()
+    );
+    Ok(())
+}
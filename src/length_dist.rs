@@ -0,0 +1,396 @@
+//! Fragment / insert length distributions.
+//!
+//! A [`LengthDist`] draws integer lengths **truncated** to `[min, max]`: continuous draws are rounded, and only the
+//! part of the distribution that rounds into the bounds is used, so the shape inside the bounds is preserved. When
+//! the bounds hold a fair share of the mass, draws outside them are simply redrawn; otherwise the truncated CDF is
+//! inverted, so even a far tail costs one draw. Bounds the distribution cannot reach at all are an error, which
+//! [`LengthDist::check`] reports before any sampling starts. Truncated means ([`LengthDist::mean`]) use closed
+//! forms, so they cost the same for any `max`.
+//!
+//! ### Specs
+//! [`LengthDist::parse`] accepts the `--length-dist` syntax:
+//! - `uniform`
+//! - `normal:MEAN,SD`
+//! - `lognormal:MEANLOG,SDLOG` (parameters of the underlying normal, as in R's `dlnorm`)
+//! - `gamma:SHAPE,SCALE`
+//! - `exponential:MEAN`
+//! - `empirical:FILE` — a TSV histogram of `length<TAB>count` lines (`#` comments and one header line allowed),
+//!   e.g. insert sizes exported from a real BAM
+
+use crate::seqio::open_input;
+use anyhow::{anyhow, Result};
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use rand_distr::{Exp, Gamma, LogNormal, Normal};
+use std::fmt;
+use std::io::BufRead;
+use std::path::Path;
+
+/// Smallest probability of the bounds for which out-of-bounds draws are redrawn rather than the CDF inverted.
+const REJECTION_MIN_MASS: f64 = 0.05;
+
+/// Distribution of fragment (or insert) lengths.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum LengthDist {
+    /// Uniform over `[min, max]`
+    #[default]
+    Uniform,
+    /// Normal with mean and standard deviation
+    Normal { mean: f64, sd: f64 },
+    /// Log-normal; `exp` of a normal with mean `meanlog` and standard deviation `sdlog`
+    LogNormal { meanlog: f64, sdlog: f64 },
+    /// Gamma with shape and scale (mean = shape × scale)
+    Gamma { shape: f64, scale: f64 },
+    /// Exponential with the given mean
+    Exponential { mean: f64 },
+    /// Histogram of `(length, weight)` bins
+    Empirical { source: String, bins: Vec<(usize, f64)> },
+}
+
+fn positive(x: f64, what: &str) -> Result<f64> {
+    if x.is_finite() && x > 0.0 { Ok(x) } else { Err(anyhow!("{} must be a positive number", what)) }
+}
+
+fn finite(x: f64, what: &str) -> Result<f64> {
+    if x.is_finite() { Ok(x) } else { Err(anyhow!("{} must be a finite number", what)) }
+}
+
+/// Complementary error function (Chebyshev fit, relative error below 1.2e-7 everywhere, tails included).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806 + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let r = t * (-z * z + poly).exp();
+    if x >= 0.0 { r } else { 2.0 - r }
+}
+
+/// Standard normal `(CDF, survival)` at `z`.
+fn normal_cdf(z: f64) -> (f64, f64) {
+    (0.5 * erfc(-z / std::f64::consts::SQRT_2), 0.5 * erfc(z / std::f64::consts::SQRT_2))
+}
+
+/// Standard normal density.
+fn normal_pdf(z: f64) -> f64 {
+    (-z * z / 2.0).exp() / (2.0 * std::f64::consts::PI).sqrt()
+}
+
+/// `ln Γ(x)` for `x > 0` (Lanczos approximation).
+fn ln_gamma(x: f64) -> f64 {
+    const COF: [f64; 6] =
+        [76.18009172947146, -86.50532032941677, 24.01409824083091, -1.231739572450155, 0.1208650973866179e-2, -0.5395239384953e-5];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let ser = COF.iter().enumerate().fold(1.000000000190015, |s, (j, c)| s + c / (x + 1.0 + j as f64));
+    -tmp + (2.5066282746310005 * ser / x).ln()
+}
+
+/// Regularized incomplete gamma functions `(P(k, x), Q(k, x))`: the gamma(`k`, 1) `(CDF, survival)` at `x`.
+fn incomplete_gamma(k: f64, x: f64) -> (f64, f64) {
+    if x <= 0.0 {
+        return (0.0, 1.0);
+    }
+    if x.is_infinite() {
+        return (1.0, 0.0);
+    }
+    let front = (k * x.ln() - x - ln_gamma(k)).exp();
+    if x < k + 1.0 {
+        // Series for P
+        let (mut a, mut term) = (k, 1.0 / k);
+        let mut sum = term;
+        for _ in 0..100_000 {
+            a += 1.0;
+            term *= x / a;
+            sum += term;
+            if term < sum * 1e-15 { break; }
+        }
+        let p = sum * front;
+        (p, 1.0 - p)
+    } else {
+        // Continued fraction for Q (modified Lentz)
+        const TINY: f64 = 1e-300;
+        let mut b = x + 1.0 - k;
+        let (mut c, mut d) = (1.0 / TINY, 1.0 / b);
+        let mut h = d;
+        for i in 1..100_000 {
+            let an = -(i as f64) * (i as f64 - k);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < TINY { d = TINY; }
+            c = b + an / c;
+            if c.abs() < TINY { c = TINY; }
+            d = 1.0 / d;
+            h *= d * c;
+            if (d * c - 1.0).abs() < 1e-15 { break; }
+        }
+        let q = front * h;
+        (1.0 - q, q)
+    }
+}
+
+/// Probability between two points given their `(CDF, survival)` pairs, taken from whichever tail keeps precision.
+fn mass((cdf_lo, sf_lo): (f64, f64), (cdf_hi, sf_hi): (f64, f64)) -> f64 {
+    if cdf_lo < 0.5 { cdf_hi - cdf_lo } else { sf_lo - sf_hi }
+}
+
+impl LengthDist {
+    /// Normal distribution; `sd` must be positive.
+    pub fn normal(mean: f64, sd: f64) -> Result<Self> {
+        Ok(LengthDist::Normal { mean: finite(mean, "normal mean")?, sd: positive(sd, "normal sd")? })
+    }
+
+    /// Parse a `--length-dist` spec (see the module docs).
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (kind, params) = spec.split_once(':').unwrap_or((spec, ""));
+        let nums = |n: usize| -> Result<Vec<f64>> {
+            let v: Vec<f64> = params
+                .split(',')
+                .map(|x| x.trim().parse::<f64>())
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|_| anyhow!("Invalid numbers in length distribution '{}'", spec))?;
+            if v.len() != n {
+                return Err(anyhow!("Length distribution '{}' needs {} parameter(s)", spec, n));
+            }
+            Ok(v)
+        };
+        match kind.to_ascii_lowercase().as_str() {
+            "uniform" => Ok(LengthDist::Uniform),
+            "normal" => {
+                let v = nums(2)?;
+                LengthDist::normal(v[0], v[1])
+            }
+            "lognormal" | "log-normal" => {
+                let v = nums(2)?;
+                Ok(LengthDist::LogNormal { meanlog: finite(v[0], "lognormal meanlog")?, sdlog: positive(v[1], "lognormal sdlog")? })
+            }
+            "gamma" => {
+                let v = nums(2)?;
+                Ok(LengthDist::Gamma { shape: positive(v[0], "gamma shape")?, scale: positive(v[1], "gamma scale")? })
+            }
+            "exponential" | "exp" => Ok(LengthDist::Exponential { mean: positive(nums(1)?[0], "exponential mean")? }),
+            "empirical" if !params.is_empty() => LengthDist::empirical(params),
+            _ => Err(anyhow!(
+                "Unknown length distribution '{}' (use uniform, normal:MEAN,SD, lognormal:MEANLOG,SDLOG, gamma:SHAPE,SCALE, exponential:MEAN or empirical:FILE)",
+                spec
+            )),
+        }
+    }
+
+    /// Load an empirical histogram from a `length<TAB>count` TSV.
+    pub fn empirical<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut bins = Vec::new();
+        let mut header_seen = false;
+        for (i, line) in open_input(path)?.lines().enumerate() {
+            let line = line?;
+            let t = line.trim();
+            if t.is_empty() || t.starts_with('#') { continue; }
+            let cols: Vec<&str> = t.split(['\t', ' ', ',']).filter(|c| !c.is_empty()).collect();
+            let parsed = match cols.as_slice() {
+                [len, count, ..] => len.parse::<usize>().ok().zip(count.parse::<f64>().ok()),
+                _ => None,
+            };
+            match parsed {
+                Some((len, w)) if w.is_finite() && w >= 0.0 => bins.push((len, w)),
+                None if !header_seen && bins.is_empty() => header_seen = true,
+                _ => return Err(anyhow!("{}:{}: expected 'length<TAB>count'", path.display(), i + 1)),
+            }
+        }
+        if !bins.iter().any(|&(_, w)| w > 0.0) {
+            return Err(anyhow!("{}: empirical length histogram is empty", path.display()));
+        }
+        Ok(LengthDist::Empirical { source: path.display().to_string(), bins })
+    }
+
+    /// Draw one length in `[min, max]`.
+    pub fn sample<R: Rng>(&self, min: usize, max: usize, rng: &mut R) -> Result<usize> {
+        match self {
+            LengthDist::Uniform => Ok(rng.gen_range(min..=max)),
+            LengthDist::Normal { mean, sd } => {
+                let d = Normal::new(*mean, *sd).map_err(|e| anyhow!("{}: {}", self, e))?;
+                self.truncated(|rng| d.sample(rng), min, max, rng)
+            }
+            LengthDist::LogNormal { meanlog, sdlog } => {
+                let d = LogNormal::new(*meanlog, *sdlog).map_err(|e| anyhow!("{}: {}", self, e))?;
+                self.truncated(|rng| d.sample(rng), min, max, rng)
+            }
+            LengthDist::Gamma { shape, scale } => {
+                let d = Gamma::new(*shape, *scale).map_err(|e| anyhow!("{}: {}", self, e))?;
+                self.truncated(|rng| d.sample(rng), min, max, rng)
+            }
+            LengthDist::Exponential { mean } => {
+                let d = Exp::new(1.0 / mean).map_err(|e| anyhow!("{}: {}", self, e))?;
+                self.truncated(|rng| d.sample(rng), min, max, rng)
+            }
+            LengthDist::Empirical { bins, .. } => {
+                let inside: Vec<&(usize, f64)> = bins.iter().filter(|(l, w)| (min..=max).contains(l) && *w > 0.0).collect();
+                let index = WeightedIndex::new(inside.iter().map(|(_, w)| *w))
+                    .map_err(|_| anyhow!("The {} length distribution has no lengths within [--min, --max] = [{}, {}]", self, min, max))?;
+                Ok(inside[index.sample(rng)].0)
+            }
+        }
+    }
+
+    /// Check that the distribution can produce a length in `[min, max]` at all, so that a run fails up front rather
+    /// than part-way through.
+    pub fn check(&self, min: usize, max: usize) -> Result<()> {
+        self.mean(min, max).map(|_| ())
+    }
+
+    /// Mean length of the distribution truncated to `[min, max]`, i.e. the expected value of [`LengthDist::sample`]
+    /// (the continuous distributions in closed form, ignoring the rounding to whole lengths).
+    pub fn mean(&self, min: usize, max: usize) -> Result<f64> {
+        let (lo, hi) = (min as f64 - 0.5, max as f64 + 0.5);
+        match *self {
+            LengthDist::Uniform => Ok((min + max) as f64 / 2.0),
+            LengthDist::Empirical { ref bins, .. } => {
+                let inside = bins.iter().filter(|(l, w)| (min..=max).contains(l) && *w > 0.0);
+                let (sum, weight) = inside.fold((0.0, 0.0), |(s, t), &(l, w)| (s + l as f64 * w, t + w));
+                if weight > 0.0 {
+                    Ok(sum / weight)
+                } else {
+                    Err(anyhow!("The {} length distribution has no lengths within [--min, --max] = [{}, {}]", self, min, max))
+                }
+            }
+            LengthDist::Normal { mean, sd } => {
+                let inside = self.inside(min, max)?;
+                Ok(mean + sd * (normal_pdf((lo - mean) / sd) - normal_pdf((hi - mean) / sd)) / inside)
+            }
+            LengthDist::LogNormal { meanlog, sdlog } => {
+                let inside = self.inside(min, max)?;
+                let z = |x: f64| if x > 0.0 { (x.ln() - meanlog) / sdlog } else { f64::NEG_INFINITY };
+                let shifted = mass(normal_cdf(z(lo) - sdlog), normal_cdf(z(hi) - sdlog));
+                Ok((meanlog + sdlog * sdlog / 2.0).exp() * shifted / inside)
+            }
+            // E[X; lo < X < hi] = shape × scale × (mass of gamma(shape + 1) over the same interval)
+            LengthDist::Gamma { shape, scale } => {
+                let inside = self.inside(min, max)?;
+                let upper = mass(incomplete_gamma(shape + 1.0, lo / scale), incomplete_gamma(shape + 1.0, hi / scale));
+                Ok(shape * scale * upper / inside)
+            }
+            LengthDist::Exponential { mean } => {
+                let inside = self.inside(min, max)?;
+                Ok(mean * mass(incomplete_gamma(2.0, lo / mean), incomplete_gamma(2.0, hi / mean)) / inside)
+            }
+        }
+    }
+
+    /// `(CDF, survival)` of a continuous distribution at `x`, each computed directly so that both tails stay accurate.
+    fn cdf(&self, x: f64) -> (f64, f64) {
+        match *self {
+            LengthDist::Normal { mean, sd } => normal_cdf((x - mean) / sd),
+            LengthDist::LogNormal { meanlog, sdlog } if x > 0.0 => normal_cdf((x.ln() - meanlog) / sdlog),
+            LengthDist::Gamma { shape, scale } => incomplete_gamma(shape, x / scale),
+            LengthDist::Exponential { mean } if x > 0.0 => (-(-x / mean).exp_m1(), (-x / mean).exp()),
+            _ => (0.0, 1.0),
+        }
+    }
+
+    /// Probability that a continuous draw rounds into `[min, max]`; an error if it is zero.
+    fn inside(&self, min: usize, max: usize) -> Result<f64> {
+        let inside = mass(self.cdf(min as f64 - 0.5), self.cdf(max as f64 + 0.5));
+        if inside > 0.0 {
+            Ok(inside)
+        } else {
+            Err(anyhow!("The {} length distribution never falls within [--min, --max] = [{}, {}]", self, min, max))
+        }
+    }
+
+    /// Draw a length in `[min, max]` from a continuous distribution (sampled with `draw`) truncated to the values that
+    /// round into the bounds: by redrawing when the bounds are likely enough, otherwise by inverting the truncated CDF
+    /// with bisection.
+    fn truncated<R: Rng, F: FnMut(&mut R) -> f64>(&self, mut draw: F, min: usize, max: usize, rng: &mut R) -> Result<usize> {
+        let inside = self.inside(min, max)?;
+        let (lo, hi) = (min as f64 - 0.5, max as f64 + 0.5);
+        if inside >= REJECTION_MIN_MASS {
+            loop {
+                let x = draw(rng).round();
+                if x >= min as f64 && x <= max as f64 {
+                    return Ok(x as usize);
+                }
+            }
+        }
+        let at_lo = self.cdf(lo);
+        let u = rng.gen::<f64>() * inside;
+        let (mut a, mut b) = (lo, hi);
+        while b - a > 1e-3 {
+            let mid = (a + b) / 2.0;
+            if mass(at_lo, self.cdf(mid)) < u { a = mid; } else { b = mid; }
+        }
+        Ok(((a + b) / 2.0).round().clamp(min as f64, max as f64) as usize)
+    }
+}
+
+impl fmt::Display for LengthDist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LengthDist::Uniform => write!(f, "uniform"),
+            LengthDist::Normal { mean, sd } => write!(f, "normal(mean={}, sd={})", mean, sd),
+            LengthDist::LogNormal { meanlog, sdlog } => write!(f, "lognormal(meanlog={}, sdlog={})", meanlog, sdlog),
+            LengthDist::Gamma { shape, scale } => write!(f, "gamma(shape={}, scale={})", shape, scale),
+            LengthDist::Exponential { mean } => write!(f, "exponential(mean={})", mean),
+            LengthDist::Empirical { source, bins } => write!(f, "empirical(file={}, bins={})", source, bins.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn draws_respect_bounds_for_every_distribution() {
+        let dir = tempdir().unwrap();
+        let tsv = dir.path().join("insert.tsv");
+        fs::write(&tsv, "insert_size\tcount\n90\t1000\n150\t5\n160\t3\n").unwrap();
+        let specs = ["uniform", "normal:150,40", "lognormal:5,0.3", "gamma:4,40", "exponential:150"];
+        let mut rng = StdRng::seed_from_u64(2);
+        for spec in specs {
+            let d = LengthDist::parse(spec).unwrap();
+            assert!((0..500).all(|_| (100..=200).contains(&d.sample(100, 200, &mut rng).unwrap())), "{}", d);
+        }
+        let emp = LengthDist::parse(&format!("empirical:{}", tsv.display())).unwrap();
+        assert!((0..100).all(|_| [150, 160].contains(&emp.sample(100, 200, &mut rng).unwrap())));
+        assert!(emp.sample(10, 20, &mut rng).is_err());
+
+        assert!(LengthDist::parse("normal:150").is_err());
+        assert!(LengthDist::parse("gamma:-1,3").is_err());
+        assert!(LengthDist::parse("normal:10000,1").unwrap().sample(100, 200, &mut rng).is_err());
+    }
+
+    #[test]
+    fn truncated_means_match_draws() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for spec in ["uniform", "normal:120,50", "lognormal:5,0.4", "gamma:2,60", "exponential:80"] {
+            let d = LengthDist::parse(spec).unwrap();
+            let mean = d.mean(50, 250).unwrap();
+            let drawn = (0..20_000).map(|_| d.sample(50, 250, &mut rng).unwrap() as f64).sum::<f64>() / 20_000.0;
+            assert!((mean - drawn).abs() < 2.0, "{}: {} vs {}", d, mean, drawn);
+        }
+        let emp = LengthDist::Empirical { source: "-".into(), bins: vec![(100, 1.0), (200, 3.0), (900, 5.0)] };
+        assert_eq!(emp.mean(1, 500).unwrap(), 175.0);
+    }
+
+    #[test]
+    fn far_tails_and_long_bounds_need_no_rejection_loop() {
+        let mut rng = StdRng::seed_from_u64(9);
+        // Bounds 15 SD above the mean, and 50 scales into a gamma tail: one draw each, nothing rejected
+        for (spec, min, max) in [("normal:150,10", 300, 400), ("gamma:2,100", 5000, 6000)] {
+            let d = LengthDist::parse(spec).unwrap();
+            let drawn = (0..5_000).map(|_| d.sample(min, max, &mut rng).unwrap()).collect::<Vec<_>>();
+            assert!(drawn.iter().all(|l| (min..=max).contains(l)));
+            let (mean, observed) = (d.mean(min, max).unwrap(), drawn.iter().sum::<usize>() as f64 / 5_000.0);
+            // Rounding to whole lengths shifts the observed mean by less than half a base
+            assert!((mean - observed).abs() < 0.5 + 0.05 * (mean - min as f64), "{}: {} vs {}", d, mean, observed);
+        }
+        // A long-read range costs the same as a short one
+        let long = LengthDist::parse("lognormal:9,1").unwrap();
+        assert!((long.mean(1, 100_000_000).unwrap() / (9.5f64).exp() - 1.0).abs() < 1e-3);
+        assert!(LengthDist::parse("normal:10000,1").unwrap().check(100, 200).is_err());
+        assert!(LengthDist::parse("exponential:50").unwrap().check(5_000, 6_000).is_ok());
+    }
+}
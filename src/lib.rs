@@ -0,0 +1,72 @@
+//! # limpet — bioinformatics library
+//!
+//! The `limpet` crate exposes the building blocks behind the `limpet` command-line tool so that Rust programs can
+//! call them directly instead of shelling out.
+//!
+//! ## Modules
+//! - [`seqio`] — record types ([`seqio::Contig`]), the streaming [`seqio::RecordReader`], and FASTA/FASTQ writers.
+//! - [`faidx`] — samtools-compatible `.fai`/`.gzi` indexes, random access to reference intervals and region extraction.
+//! - [`bed`] — minimal BED interval reader.
+//! - [`annotation`] — minimal GTF/GFF3 reader (spliced transcripts), transcript expression and RNA-seq fragments.
+//! - [`vcf`] — minimal VCF reader and on-demand haplotypes with variants applied, lifted back to the reference.
+//! - [`amplicon`] — in-silico PCR: degenerate primer binding sites, amplicons and amplicon reads.
+//! - [`mutate`] — random SNPs, indels and structural events with a truth VCF ([`mutate::mutate_sequence`]).
+//! - [`seq_sample`] — sample random genomic intervals ([`seq_sample::sample_fragments`]).
+//! - [`fragment`] — sampled fragments with their provenance ([`fragment::Fragment`]) and their output as reads.
+//! - [`length_dist`] — fragment/insert length distributions truncated to `[min, max]`.
+//! - [`duplicates`] — PCR duplicate families of sampled fragments, with UMIs and UMI errors.
+//! - [`simulate`] — sequencing error and quality models, and paired-end reads.
+//! - [`scramble`] — shuffle records from many inputs with provenance-rich headers.
+//! - [`strip`] — reduce headers to accession tokens.
+//! - [`sample`] — reservoir sampling of raw records.
+//! - [`validate`] — strict alphabet/format validation with located errors.
+//! - [`bgzf`] — blocked gzip writer used for `--compress bgzf` / `.bgz` outputs, and `.gzi` block indexes.
+//!
+//! Each command module also carries the `clap` argument struct and a `run` function used by the CLI; library
+//! callers should prefer the typed functions, which return results instead of writing files.
+//!
+//! ## Example
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use limpet::seqio::RecordReader;
+//! use limpet::seq_sample::{sample_fragments, SeqSampleParams};
+//! use rand::{rngs::StdRng, SeedableRng};
+//!
+//! let contigs = RecordReader::from_path("genome.fa.gz")?.collect::<anyhow::Result<Vec<_>>>()?;
+//! let params = SeqSampleParams { n: 10, min: 100, max: 300, ..Default::default() };
+//! let mut rng = StdRng::seed_from_u64(1);
+//! for frag in sample_fragments(&contigs, &params, &mut rng)? {
+//!     println!("{}\t{}\t{}", frag.contig, frag.start, frag.end);
+//! }
+//! # Ok(()) }
+//! ```
+
+pub mod amplicon;
+pub mod annotation;
+pub mod bed;
+pub mod bgzf;
+pub mod duplicates;
+pub mod faidx;
+pub mod fragment;
+pub mod length_dist;
+pub mod mutate;
+pub mod sample;
+pub mod scramble;
+pub mod seq_sample;
+pub mod seqio;
+pub mod simulate;
+pub mod strip;
+pub mod validate;
+pub mod vcf;
+
+/// Command arguments parsed from `argv` (without the program name), with the CLI defaults for everything else, so
+/// tests keep working when options are added.
+#[cfg(test)]
+pub(crate) fn parse_args<A: clap::Args>(argv: &[&str]) -> A {
+    #[derive(clap::Parser)]
+    struct Cli<A: clap::Args> {
+        #[command(flatten)]
+        args: A,
+    }
+    <Cli<A> as clap::Parser>::parse_from(std::iter::once("limpet").chain(argv.iter().copied())).args
+}
@@ -4,12 +4,24 @@
 //! manipulation written in Rust. It emphasizes **clarity**, **safety**, and **reproducibility**.
 //!
 //! ## Subcommands (overview)
-//! - **`seq_sample`** — sample *n* random genomic intervals from a reference and write FASTA.
-//! - **`scramble`** — ingest many FASTA/FASTQ (plain or `.gz`), randomize global order, write one FASTA;
-//!   headers are rewritten to `scramble_00001` with provenance retained.
+//! - **`seq_sample`** — sample *n* random genomic intervals from a reference and write FASTA, or FASTQ reads with
+//!   simulated errors and qualities (`--error-model illumina|long-read`), single or paired-end (`--paired`),
+//!   optionally from haplotypes carrying the variants of a VCF (`--vcf`) or from the spliced, expression-weighted
+//!   transcripts of a GTF/GFF3 (`--annotation`), with optional PCR duplicate families and UMIs (`--duplicates`).
+//! - **`amplicon`** — in-silico PCR: find (degenerate, mismatch-tolerant) primer pair sites in a reference and write
+//!   the amplicons at given copy numbers, or reads simulated from them; reports primer hits per contig.
+//! - **`mutate`** — apply random SNPs, small indels and optional inversions, duplications and deletions to a FASTA,
+//!   writing the mutated FASTA and a truth VCF.
+//! - **`scramble`** — stream many FASTA/FASTQ (plain or compressed), randomize global order, write a single file
+//!   (FASTQ when every input is FASTQ, FASTA otherwise; optionally compressed with `--compress`); headers are
+//!   rewritten to `scramble_00001` with provenance retained.
 //! - **`strip`** — reduce FASTA headers to the accession token only (first whitespace‑separated token).
 //! - **`sample`** — randomly pick *n* raw records from the input (FASTA or FASTQ) and write them **unmodified**,
 //!   preserving the file format; gzip if output ends with `.gz`.
+//! - **`faidx`** — build a samtools-compatible `.fai` index (plus `.gzi` for BGZF) for a FASTA reference, or
+//!   extract regions (`chr1:10001-10120`, BED intervals, or `seq_sample` headers), optionally reverse-complemented.
+//! - **`validate`** — check FASTA/FASTQ against a DNA/RNA/IUPAC/protein alphabet and print a JSON report with
+//!   file, line and record for every problem.
 //!
 //! ## Installation
 //! ```bash
@@ -23,19 +35,13 @@
 //! `limpet` is intended for **in silico** education and analysis. It does not interact with lab equipment
 //! and does not attempt to evaluate biological risk. Prefer non‑pathogenic and openly available reference data,
 //! and follow your institution's biosafety and data governance policies.
-
 //!
-//! Subcommands:
-//! - `seq_sample`: sample random sequences from a reference FASTA
-
-mod seqio;
-mod seq_sample;
-mod scramble;
-mod sample;
-mod strip;
+//! ## Library
+//! The command logic lives in the `limpet` library crate; this binary is a thin `clap` front end over it.
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use limpet::{amplicon, faidx, mutate, sample, scramble, seq_sample, strip, validate};
 
 /// limpet CLI
 #[derive(Parser, Debug)]
@@ -51,22 +57,34 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Sample random sequences from a reference FASTA
-    SeqSample(seq_sample::SeqSampleArgs),
+    SeqSample(Box<seq_sample::SeqSampleArgs>),
+    /// Simulate PCR amplicons (and optionally reads) from primer pairs
+    Amplicon(amplicon::AmpliconArgs),
+    /// Apply random SNPs, indels and structural events to a FASTA, with a truth VCF
+    Mutate(mutate::MutateArgs),
     /// Randomly sample N records from an input, keeping original format
     Sample(sample::SampleArgs),
     /// Strip FASTA headers to accession-only
     Strip(strip::StripArgs),
-    /// Scramble sequences from multiple inputs into one FASTA
+    /// Scramble sequences from multiple inputs into a single FASTA or FASTQ file
     Scramble(scramble::ScrambleArgs),
+    /// Validate FASTA/FASTQ files against an alphabet and report located errors
+    Validate(validate::ValidateArgs),
+    /// Index a FASTA for random access (.fai/.gzi), or extract regions from it
+    Faidx(faidx::FaidxArgs),
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
     match cli.command {
-        Commands::SeqSample(args) => seq_sample::run(args)?,
+        Commands::SeqSample(args) => seq_sample::run(*args)?,
+        Commands::Amplicon(args) => amplicon::run(args)?,
+        Commands::Mutate(args) => mutate::run(args)?,
         Commands::Scramble(args) => scramble::run(args)?,
         Commands::Strip(args) => strip::run(args)?,
         Commands::Sample(args) => sample::run(args)?,
+        Commands::Validate(args) => validate::run(args)?,
+        Commands::Faidx(args) => faidx::run(args)?,
     }
     Ok(())
 }
@@ -0,0 +1,447 @@
+//! Random mutation simulator (`mutate`).
+//!
+//! Streams a FASTA (plain or compressed) and applies random **SNPs**, small **insertions/deletions** and, optionally,
+//! larger **structural events** (inversions, tandem duplications, deletions) to every contig, writing the mutated
+//! FASTA and a **truth VCF** in the input's coordinates. Headers are kept, so VCF `CHROM` names match the output.
+//!
+//! ### Rates
+//! All rates are per reference base. Event counts per contig are Poisson with mean `rate × length`; positions are
+//! uniform. Events never overlap (nor share an indel's anchor base), so a few candidates may be dropped; only
+//! `A`, `C`, `G` and `T` are ever mutated or used as an anchor, and a structural event is dropped if any base of its
+//! span is something else, e.g. an assembly gap. SNPs switch to one of the three other bases;
+//! indel lengths are uniform in `1..=--max-indel`, structural lengths in `[--sv-min, --sv-max]`.
+//!
+//! ### Truth VCF
+//! Small variants are written as sequence alleles (sites-only, so `seq-sample --vcf` applies them as-is).
+//! Structural events use symbolic alleles (`<DEL>`, `<INV>`, `<DUP:TANDEM>`) with `SVTYPE`, `END` and `SVLEN`;
+//! `POS` is the base before the event and the duplicated copy follows the original.
+//!
+//! ### Example
+//! ```text
+//! limpet mutate -i ref.fa -o mutated.fa --truth-vcf truth.vcf --snp-rate 0.001 --indel-rate 0.0001 \
+//!     --inversion-rate 1e-6 --sv-min 1000 --sv-max 10000 --seed 7
+//! ```
+
+use crate::seqio::{create_output, create_output_with, reverse_complement, Codec, FastaWriter, RecordReader};
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use rand::prelude::*;
+use rand_distr::Poisson;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Args, Debug, Clone)]
+pub struct MutateArgs {
+    /// Input FASTA (optionally compressed; `-` for stdin)
+    #[arg(short = 'i', long = "input", value_name = "FASTA")]
+    pub input: PathBuf,
+
+    /// Output FASTA with the mutations applied (`-` for stdout)
+    #[arg(short = 'o', long = "output", value_name = "FASTA")]
+    pub output: PathBuf,
+
+    /// Truth VCF of the applied mutations, in input coordinates (gzipped if the name ends with .gz)
+    #[arg(long = "truth-vcf", value_name = "VCF")]
+    pub truth_vcf: PathBuf,
+
+    /// SNP rate per base
+    #[arg(long = "snp-rate", value_name = "FLOAT", default_value_t = 0.001)]
+    pub snp_rate: f64,
+
+    /// Small insertion/deletion rate per base (half each)
+    #[arg(long = "indel-rate", value_name = "FLOAT", default_value_t = 0.0001)]
+    pub indel_rate: f64,
+
+    /// Maximum small indel length
+    #[arg(long = "max-indel", value_name = "INT", default_value_t = 10)]
+    pub max_indel: usize,
+
+    /// Inversion rate per base
+    #[arg(long = "inversion-rate", value_name = "FLOAT", default_value_t = 0.0)]
+    pub inversion_rate: f64,
+
+    /// Tandem duplication rate per base
+    #[arg(long = "duplication-rate", value_name = "FLOAT", default_value_t = 0.0)]
+    pub duplication_rate: f64,
+
+    /// Large deletion rate per base
+    #[arg(long = "deletion-rate", value_name = "FLOAT", default_value_t = 0.0)]
+    pub deletion_rate: f64,
+
+    /// Minimum structural event length
+    #[arg(long = "sv-min", value_name = "INT", default_value_t = 1000)]
+    pub sv_min: usize,
+
+    /// Maximum structural event length
+    #[arg(long = "sv-max", value_name = "INT", default_value_t = 10000)]
+    pub sv_max: usize,
+
+    /// Optional RNG seed for reproducibility
+    #[arg(long = "seed", value_name = "INT")]
+    pub seed: Option<u64>,
+
+    /// Output compression for the FASTA (default: from the output file extension; plain for stdout)
+    #[arg(long = "compress", value_enum, value_name = "CODEC")]
+    pub compress: Option<Codec>,
+}
+
+/// Typed parameters for [`mutate_sequence`].
+#[derive(Debug, Clone)]
+pub struct MutateParams {
+    pub snp_rate: f64,
+    pub indel_rate: f64,
+    pub max_indel: usize,
+    pub inversion_rate: f64,
+    pub duplication_rate: f64,
+    pub deletion_rate: f64,
+    pub sv_min: usize,
+    pub sv_max: usize,
+}
+
+impl From<&MutateArgs> for MutateParams {
+    fn from(args: &MutateArgs) -> Self {
+        MutateParams {
+            snp_rate: args.snp_rate,
+            indel_rate: args.indel_rate,
+            max_indel: args.max_indel,
+            inversion_rate: args.inversion_rate,
+            duplication_rate: args.duplication_rate,
+            deletion_rate: args.deletion_rate,
+            sv_min: args.sv_min,
+            sv_max: args.sv_max,
+        }
+    }
+}
+
+impl MutateParams {
+    fn validate(&self) -> Result<()> {
+        let rates = [
+            ("--snp-rate", self.snp_rate),
+            ("--indel-rate", self.indel_rate),
+            ("--inversion-rate", self.inversion_rate),
+            ("--duplication-rate", self.duplication_rate),
+            ("--deletion-rate", self.deletion_rate),
+        ];
+        for (flag, rate) in rates {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(anyhow!("{} must be in [0, 1]", flag));
+            }
+        }
+        if self.max_indel == 0 {
+            return Err(anyhow!("--max-indel must be greater than 0"));
+        }
+        if self.sv_min == 0 {
+            return Err(anyhow!("--sv-min must be greater than 0"));
+        }
+        if self.sv_min > self.sv_max {
+            return Err(anyhow!("--sv-min must be <= --sv-max"));
+        }
+        Ok(())
+    }
+}
+
+/// Kind of a [`Mutation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationKind {
+    Snp,
+    Insertion,
+    Deletion,
+    Inversion,
+    Duplication,
+    LargeDeletion,
+}
+
+impl MutationKind {
+    /// True for inversions, duplications and large deletions.
+    pub fn is_structural(self) -> bool {
+        matches!(self, MutationKind::Inversion | MutationKind::Duplication | MutationKind::LargeDeletion)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            MutationKind::Snp => "SNPs",
+            MutationKind::Insertion => "insertions",
+            MutationKind::Deletion => "deletions",
+            MutationKind::Inversion => "inversions",
+            MutationKind::Duplication => "duplications",
+            MutationKind::LargeDeletion => "large deletions",
+        }
+    }
+}
+
+const KINDS: [MutationKind; 6] = [
+    MutationKind::Snp,
+    MutationKind::Insertion,
+    MutationKind::Deletion,
+    MutationKind::Inversion,
+    MutationKind::Duplication,
+    MutationKind::LargeDeletion,
+];
+
+/// One mutation: reference bases `[pos, pos + ref_len)` are replaced by `alt`. Indels and structural events
+/// include their anchor base at `pos`, as in VCF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mutation {
+    pub kind: MutationKind,
+    /// 0-based position of the first replaced base
+    pub pos: usize,
+    pub ref_len: usize,
+    pub alt: Vec<u8>,
+}
+
+fn is_acgt(b: u8) -> bool {
+    matches!(b, b'A' | b'C' | b'G' | b'T')
+}
+
+fn random_base<R: Rng>(rng: &mut R) -> u8 {
+    b"ACGT"[rng.gen_range(0..4)]
+}
+
+/// Poisson-distributed event count with mean `rate × len`.
+fn event_count<R: Rng>(rate: f64, len: usize, rng: &mut R) -> usize {
+    let mean = rate * len as f64;
+    if mean <= 0.0 { return 0; }
+    Poisson::new(mean).map(|p| p.sample(rng) as usize).unwrap_or(0)
+}
+
+/// Draw one candidate of `kind` on `seq`, or `None` if it does not fit or any base it replaces (including the whole
+/// span of a structural event) is not ACGT.
+fn candidate<R: Rng>(seq: &[u8], kind: MutationKind, p: &MutateParams, rng: &mut R) -> Option<Mutation> {
+    let len = match kind {
+        MutationKind::Snp => 0,
+        MutationKind::Insertion | MutationKind::Deletion => rng.gen_range(1..=p.max_indel),
+        _ => rng.gen_range(p.sv_min..=p.sv_max),
+    };
+    let span = match kind {
+        MutationKind::Snp | MutationKind::Insertion => 1,
+        _ => len + 1,
+    };
+    if span > seq.len() { return None; }
+    let pos = rng.gen_range(0..=seq.len() - span);
+    if !seq[pos..pos + span].iter().all(|&b| is_acgt(b)) { return None; }
+    let anchor = seq[pos];
+    let alt = match kind {
+        MutationKind::Snp => {
+            let others: Vec<u8> = b"ACGT".iter().copied().filter(|&b| b != anchor).collect();
+            vec![*others.choose(rng)?]
+        }
+        MutationKind::Insertion => std::iter::once(anchor).chain((0..len).map(|_| random_base(rng))).collect(),
+        MutationKind::Deletion | MutationKind::LargeDeletion => vec![anchor],
+        MutationKind::Inversion => {
+            let mut alt = vec![anchor];
+            alt.extend(reverse_complement(&seq[pos + 1..pos + span]));
+            alt
+        }
+        MutationKind::Duplication => {
+            let mut alt = seq[pos..pos + span].to_vec();
+            alt.extend_from_slice(&seq[pos + 1..pos + span]);
+            alt
+        }
+    };
+    Some(Mutation { kind, pos, ref_len: span, alt })
+}
+
+/// Draw random mutations for one sequence, sorted by position and non-overlapping.
+/// Structural events are placed first; later candidates that overlap an accepted event are dropped.
+pub fn mutate_sequence<R: Rng>(seq: &[u8], params: &MutateParams, rng: &mut R) -> Result<Vec<Mutation>> {
+    params.validate()?;
+    let rates = [
+        (MutationKind::Inversion, params.inversion_rate),
+        (MutationKind::Duplication, params.duplication_rate),
+        (MutationKind::LargeDeletion, params.deletion_rate),
+        (MutationKind::Snp, params.snp_rate),
+        (MutationKind::Insertion, params.indel_rate / 2.0),
+        (MutationKind::Deletion, params.indel_rate / 2.0),
+    ];
+    // Accepted mutations keyed by start; they never overlap, so only the last one starting before a
+    // candidate's end can collide with it.
+    let mut accepted: BTreeMap<usize, Mutation> = BTreeMap::new();
+    for (kind, rate) in rates {
+        for _ in 0..event_count(rate, seq.len(), rng) {
+            let Some(m) = candidate(seq, kind, params, rng) else { continue };
+            let end = m.pos + m.ref_len;
+            let clash = accepted.range(..end).next_back().is_some_and(|(_, prev)| prev.pos + prev.ref_len > m.pos);
+            if clash { continue; }
+            accepted.insert(m.pos, m);
+        }
+    }
+    Ok(accepted.into_values().collect())
+}
+
+/// Apply sorted, non-overlapping mutations to `seq`.
+pub fn apply_mutations(seq: &[u8], mutations: &[Mutation]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(seq.len());
+    let mut cursor = 0;
+    for m in mutations {
+        out.extend_from_slice(&seq[cursor..m.pos]);
+        out.extend_from_slice(&m.alt);
+        cursor = m.pos + m.ref_len;
+    }
+    out.extend_from_slice(&seq[cursor..]);
+    out
+}
+
+/// One truth VCF line for `m` on contig `chrom` of `seq`.
+fn vcf_line(chrom: &str, seq: &[u8], m: &Mutation) -> String {
+    let pos = m.pos + 1;
+    if m.kind.is_structural() {
+        let sv_len = m.ref_len - 1;
+        let (alt, svtype, svlen) = match m.kind {
+            MutationKind::Inversion => ("<INV>", "INV", sv_len as i64),
+            MutationKind::Duplication => ("<DUP:TANDEM>", "DUP", sv_len as i64),
+            _ => ("<DEL>", "DEL", -(sv_len as i64)),
+        };
+        format!(
+            "{}\t{}\t.\t{}\t{}\t.\tPASS\tSVTYPE={};END={};SVLEN={}\n",
+            chrom, pos, seq[m.pos] as char, alt, svtype, m.pos + m.ref_len, svlen
+        )
+    } else {
+        format!(
+            "{}\t{}\t.\t{}\t{}\t.\tPASS\t.\n",
+            chrom,
+            pos,
+            String::from_utf8_lossy(&seq[m.pos..m.pos + m.ref_len]),
+            String::from_utf8_lossy(&m.alt)
+        )
+    }
+}
+
+/// Write the truth VCF: meta lines, one `##contig` line per contig, then the buffered records.
+fn write_truth_vcf(args: &MutateArgs, contigs: &[(String, usize)], body: &[u8]) -> Result<()> {
+    let mut w = create_output(&args.truth_vcf)?;
+    writeln!(w, "##fileformat=VCFv4.2")?;
+    writeln!(w, "##source=limpet mutate")?;
+    for (name, len) in contigs {
+        writeln!(w, "##contig=<ID={},length={}>", name, len)?;
+    }
+    writeln!(w, "##INFO=<ID=SVTYPE,Number=1,Type=String,Description=\"Type of structural variant\">")?;
+    writeln!(w, "##INFO=<ID=END,Number=1,Type=Integer,Description=\"End position of the variant\">")?;
+    writeln!(w, "##INFO=<ID=SVLEN,Number=1,Type=Integer,Description=\"Length difference of the variant\">")?;
+    writeln!(w, "##ALT=<ID=DEL,Description=\"Deletion\">")?;
+    writeln!(w, "##ALT=<ID=INV,Description=\"Inversion\">")?;
+    writeln!(w, "##ALT=<ID=DUP:TANDEM,Description=\"Tandem duplication\">")?;
+    writeln!(w, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO")?;
+    w.write_all(body)?;
+    w.close()
+}
+
+/// Execute the `mutate` subcommand.
+/// Streams contigs, mutates each one, writes the mutated FASTA and then the truth VCF.
+pub fn run(args: MutateArgs) -> Result<()> {
+    let params = MutateParams::from(&args);
+    params.validate()?;
+
+    let mut rng: StdRng = match args.seed {
+        Some(s) => StdRng::seed_from_u64(s),
+        None => StdRng::from_rng(thread_rng()).context("Failed to initialize RNG")?,
+    };
+
+    let reader = RecordReader::from_path(&args.input)?;
+    let mut writer = FastaWriter::new(create_output_with(&args.output, args.compress)?, 80);
+    let mut contigs: Vec<(String, usize)> = Vec::new();
+    let mut body: Vec<u8> = Vec::new();
+    let mut counts = [0usize; KINDS.len()];
+    for rec in reader {
+        let rec = rec.with_context(|| format!("Failed to read {}", args.input.display()))?;
+        let mutations = mutate_sequence(&rec.seq, &params, &mut rng)?;
+        for m in &mutations {
+            counts[KINDS.iter().position(|&k| k == m.kind).unwrap_or(0)] += 1;
+            body.extend_from_slice(vcf_line(&rec.name, &rec.seq, m).as_bytes());
+        }
+        writer.write_record(&rec.header, &apply_mutations(&rec.seq, &mutations))?;
+        contigs.push((rec.name, rec.seq.len()));
+    }
+    writer.finish()?.close()?;
+    if contigs.is_empty() {
+        return Err(anyhow!("No sequences found in {}", args.input.display()));
+    }
+    write_truth_vcf(&args, &contigs, &body)?;
+
+    let summary: Vec<String> = KINDS.iter().zip(counts).map(|(k, c)| format!("{} {}", c, k.name())).collect();
+    eprintln!(
+        "Applied {} to {} contigs; wrote {} and {}",
+        summary.join(", "),
+        contigs.len(),
+        args.output.display(),
+        args.truth_vcf.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::faidx::Reference;
+    use crate::parse_args;
+    use crate::seqio::SeqMode;
+    use crate::vcf::{build_haplotypes, read_vcf};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn structural_events_rewrite_the_sequence() {
+        let seq = b"AACCGGTTAA";
+        let m = |kind, pos, ref_len, alt: &[u8]| Mutation { kind, pos, ref_len, alt: alt.to_vec() };
+        let inv = m(MutationKind::Inversion, 1, 4, b"ACGG");
+        let dup = m(MutationKind::Duplication, 5, 3, b"GTTTT");
+        assert_eq!(apply_mutations(seq, &[inv.clone(), dup]), b"AACGGGTTTTAA");
+        let del = m(MutationKind::LargeDeletion, 1, 6, b"A");
+        assert_eq!(apply_mutations(seq, std::slice::from_ref(&del)), b"AATAA");
+        assert_eq!(vcf_line("c", seq, &inv), "c\t2\t.\tA\t<INV>\t.\tPASS\tSVTYPE=INV;END=5;SVLEN=3\n");
+        assert_eq!(vcf_line("c", seq, &del), "c\t2\t.\tA\t<DEL>\t.\tPASS\tSVTYPE=DEL;END=7;SVLEN=-5\n");
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let seq: Vec<u8> = (0..20_000).map(|_| random_base(&mut rng)).collect();
+        let args = parse_args(&["-i", "in.fa", "-o", "out.fa", "--truth-vcf", "t.vcf", "--sv-min", "5", "--sv-max", "50"]);
+        let params = MutateParams { inversion_rate: 1e-3, duplication_rate: 1e-3, ..MutateParams::from(&args) };
+        let muts = mutate_sequence(&seq, &params, &mut rng).unwrap();
+        assert!(muts.iter().any(|m| m.kind == MutationKind::Duplication));
+        assert!(muts.windows(2).all(|w| w[0].pos + w[0].ref_len <= w[1].pos));
+        for m in muts.iter().filter(|m| m.kind == MutationKind::Inversion) {
+            assert_eq!(m.alt[1..], reverse_complement(&seq[m.pos + 1..m.pos + m.ref_len])[..]);
+        }
+
+        // Structural events never reach into a gap, not only through their anchor base
+        let mut gapped = seq.clone();
+        gapped[10_000..10_010].fill(b'N');
+        let params = MutateParams { snp_rate: 0.0, indel_rate: 0.0, deletion_rate: 1e-2, ..params };
+        let muts = mutate_sequence(&gapped, &params, &mut rng).unwrap();
+        assert!(muts.len() > 10);
+        assert!(muts.iter().all(|m| m.pos + m.ref_len <= 10_000 || m.pos >= 10_010));
+    }
+
+    #[test]
+    fn truth_vcf_reproduces_the_mutated_fasta() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("ref.fa");
+        let output = dir.path().join("mut.fa");
+        let truth = dir.path().join("truth.vcf");
+        let mut rng = StdRng::seed_from_u64(9);
+        let mut fasta = String::new();
+        for name in ["a", "b"] {
+            let seq: String = (0..5000).map(|_| random_base(&mut rng) as char).collect();
+            fasta.push_str(&format!(">{} desc\n{}NNNN\n", name, seq));
+        }
+        fs::write(&input, fasta).unwrap();
+
+        let path = |p: &PathBuf| p.to_str().unwrap().to_string();
+        let (input_arg, output_arg, truth_arg) = (path(&input), path(&output), path(&truth));
+        let args = parse_args(&[
+            "-i", &input_arg, "-o", &output_arg, "--truth-vcf", &truth_arg, "--snp-rate", "0.01", "--indel-rate", "0.005",
+            "--seed", "1",
+        ]);
+        run(args).unwrap();
+
+        let mutated: Vec<_> = RecordReader::from_path(&output).unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(mutated[0].header, "a desc");
+        let vcf = read_vcf(&truth, None).unwrap();
+        assert!(vcf.variants.len() > 50);
+        assert!(vcf.variants.iter().all(|v| !v.ref_allele.contains(&b'N')));
+        let mut reference = Reference::open(&input, SeqMode::Normalize).unwrap();
+        let (haplotypes, _) = build_haplotypes(&mut reference, &vcf).unwrap();
+        for (hap, rec) in haplotypes.iter().zip(&mutated) {
+            assert_eq!(hap.fetch(&mut reference, 0, hap.len()).unwrap(), rec.seq);
+        }
+    }
+}
@@ -1,86 +1,262 @@
 //! Sequence scrambler (`scramble`).
 //!
-//! Reads multiple **FASTA/FASTQ** files (plain or `.gz`), **loads all sequences into memory**, shuffles the global order,
-//! and writes a single FASTA. Each output header begins with a new sequential accession (`scramble_00001`), followed by
-//! `src=<original_accession>` and `file=<source_file>`, and finally the original header text.
+//! Reads multiple **FASTA/FASTQ** files (plain or `.gz`), shuffles the global order of all records, and writes a
+//! single file: FASTQ (with qualities) when every input is FASTQ, otherwise FASTA. Each output header begins with a
+//! new sequential accession (`scramble_00001`), followed by `src=<original_accession>` and `file=<source_file>`, and
+//! finally the original header text.
 //!
 //! ### Memory considerations
-//! This command is explicitly **in‑memory**. Handling ≳1 Gbp of sequence is reasonable on a modern laptop, but for very
-//! large datasets consider running in batches.
+//! Inputs are **streamed**. Every record is scattered to a uniformly random *bucket* file in a temporary
+//! directory; each bucket is then loaded on its own, shuffled, and appended to the output. Scattering to random
+//! buckets followed by an in‑bucket shuffle yields a uniformly random permutation, while memory stays bounded by
+//! the size of one bucket (about 256 MiB of sequence by default). Temporary space roughly equal to the
+//! uncompressed input is needed next to the output (or under `--tmp-dir`).
 //!
 //! ### Example
 //! ```text
 //! limpet scramble input1.fa input2.fq.gz -o scrambled.fa --seed 42
 //! ```
 
-use crate::seqio::{read_sequences, write_fasta, FastaRecord};
+use crate::seqio::{
+    create_output_with, is_stdio, read_sequences_with, sniff_codec, Codec, Contig, Format, Output, RecordReader,
+    RecordWriter, SeqMode,
+};
 use anyhow::{anyhow, Context, Result};
 use clap::Args;
 use rand::prelude::*;
-use std::path::PathBuf;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
 
-/// Scramble: read multiple inputs (FASTA/FASTQ and .gz variants), shuffle all records, and write a single FASTA.
+/// Approximate number of uncompressed input bytes per shuffle bucket.
+const BUCKET_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Assumed expansion ratio of compressed inputs when estimating their uncompressed size.
+const COMPRESSED_EXPANSION: u64 = 4;
+
+/// Assumed uncompressed size of stdin, whose length cannot be known up front.
+const STDIN_ESTIMATE: u64 = 16 * 1024 * 1024 * 1024;
+
+/// Scramble: read multiple inputs (FASTA/FASTQ and .gz variants), shuffle all records, and write a single file.
 #[derive(Args, Debug, Clone)]
 pub struct ScrambleArgs {
-    /// One or more input files (FASTA/FASTQ/FASTA.GZ/FASTQ.GZ)
+    /// One or more input files (FASTA/FASTQ/FASTA.GZ/FASTQ.GZ); `-` reads stdin
     #[arg(value_name = "INPUT", required = true)]
     pub inputs: Vec<PathBuf>,
 
-    /// Output FASTA path
-    #[arg(short = 'o', long = "output", value_name = "FASTA")]
+    /// Output path (FASTQ if all inputs are FASTQ, FASTA otherwise); `-` writes stdout
+    #[arg(short = 'o', long = "output", value_name = "OUTPUT")]
     pub output: PathBuf,
 
     /// Optional RNG seed for reproducibility
     #[arg(long = "seed", value_name = "INT")]
     pub seed: Option<u64>,
+
+    /// Directory for temporary shuffle buckets (defaults to the output's directory, or the system temp dir for stdout)
+    #[arg(long = "tmp-dir", value_name = "DIR")]
+    pub tmp_dir: Option<PathBuf>,
+
+    /// Output compression (default: from the output file extension; plain for stdout)
+    #[arg(long = "compress", value_enum, value_name = "CODEC")]
+    pub compress: Option<Codec>,
+
+    /// Keep original case and non-letter symbols (soft-masking, `-` gaps) instead of upper-casing
+    #[arg(long = "keep-case")]
+    pub keep_case: bool,
+}
+
+/// Estimate how many buckets are needed to keep each one near `BUCKET_BYTES`.
+pub fn bucket_count(inputs: &[PathBuf]) -> Result<usize> {
+    let mut total: u64 = 0;
+    for path in inputs {
+        if is_stdio(path) {
+            total = total.saturating_add(STDIN_ESTIMATE);
+            continue;
+        }
+        let meta = std::fs::metadata(path)
+            .with_context(|| format!("Failed to open input: {}", path.display()))?;
+        let size = if sniff_codec(path)?.is_compressed() { meta.len().saturating_mul(COMPRESSED_EXPANSION) } else { meta.len() };
+        total = total.saturating_add(size);
+    }
+    Ok(total.div_ceil(BUCKET_BYTES).max(1) as usize)
+}
+
+/// Provenance suffix for a record read from `source`: `src=<accession> file=<source> | <header>`.
+pub fn provenance(rec: &Contig, source: &str) -> String {
+    format!("src={} file={} | {}", rec.name, source, rec.header)
+}
+
+/// Output format for a set of inputs: FASTQ only when every input is FASTQ.
+pub fn output_format<I: IntoIterator<Item = Format>>(formats: I) -> Format {
+    if formats.into_iter().all(|f| f == Format::Fastq) { Format::Fastq } else { Format::Fasta }
+}
+
+/// In-memory scramble: shuffle all records from `(source, records)` pairs and rename them
+/// `scramble_00001..N` with provenance retained in the header.
+pub fn scramble_records<R: Rng>(inputs: Vec<(String, Vec<Contig>)>, rng: &mut R) -> Vec<Contig> {
+    let mut all: Vec<Contig> = Vec::new();
+    for (source, recs) in inputs {
+        for mut c in recs {
+            c.header = provenance(&c, &source);
+            all.push(c);
+        }
+    }
+    all.shuffle(rng);
+    for (i, c) in all.iter_mut().enumerate() {
+        c.name = format!("scramble_{:05}", i + 1);
+        c.header = format!("{} {}", c.name, c.header);
+    }
+    all
+}
+
+/// Summary returned by [`scramble_streams`].
+#[derive(Debug, Clone, Copy)]
+pub struct ScrambleSummary {
+    /// Number of records written
+    pub records: usize,
+    /// Format of the written output
+    pub format: Format,
+}
+
+/// Streaming scramble with bounded memory.
+///
+/// Scatters records from `(source, reader)` pairs into `buckets` temporary files under `tmp_dir`, then shuffles
+/// each bucket and writes it to `out` (FASTQ when all inputs are FASTQ, otherwise FASTA wrapped at 80 columns).
+pub fn scramble_streams<B: BufRead, W: Write, R: Rng>(
+    inputs: Vec<(String, RecordReader<B>)>,
+    tmp_dir: &Path,
+    buckets: usize,
+    out: W,
+    rng: &mut R,
+) -> Result<ScrambleSummary> {
+    let format = output_format(inputs.iter().map(|(_, r)| r.format()));
+    let n_buckets = buckets.max(1);
+    let tmp = tempfile::Builder::new()
+        .prefix(".limpet-scramble-")
+        .tempdir_in(tmp_dir)
+        .with_context(|| format!("Failed to create temporary directory in {}", tmp_dir.display()))?;
+
+    // Scatter: stream every input record (+ provenance) into a uniformly random bucket
+    let bucket_paths: Vec<PathBuf> = (0..n_buckets).map(|i| tmp.path().join(format!("bucket_{:05}", i))).collect();
+    let mut writers: Vec<RecordWriter<Output>> = bucket_paths
+        .iter()
+        .map(|p| RecordWriter::create(p, format, 0))
+        .collect::<Result<_>>()?;
+    let mut bucket_sizes: Vec<usize> = vec![0; n_buckets];
+
+    let mut total = 0usize;
+    for (source, reader) in inputs {
+        for rec in reader {
+            let c = rec.with_context(|| format!("Failed to read input {}", source))?;
+            let b = rng.gen_range(0..n_buckets);
+            writers[b].write_record(&provenance(&c, &source), &c.seq, c.qual.as_deref())?;
+            bucket_sizes[b] += 1;
+            total += 1;
+        }
+    }
+    for w in writers {
+        w.finish()?.close()?;
+    }
+    if total == 0 {
+        return Err(anyhow!("No sequences found in provided inputs."));
+    }
+
+    // Gather: shuffle each bucket in memory and append with new headers scramble_00001..N
+    let mut out = RecordWriter::new(out, format, 80);
+    let mut next_id = 1usize;
+    for (path, &size) in bucket_paths.iter().zip(&bucket_sizes) {
+        if size == 0 { continue; }
+        // Buckets already hold processed sequence; read them back verbatim
+        let mut recs = read_sequences_with(path, SeqMode::Preserve)?;
+        recs.shuffle(rng);
+        for c in recs {
+            let hdr = format!("scramble_{:05} {}", next_id, c.header);
+            out.write_record(&hdr, &c.seq, c.qual.as_deref())?;
+            next_id += 1;
+        }
+    }
+    out.finish()?.flush()?;
+
+    Ok(ScrambleSummary { records: total, format })
 }
 
 /// Execute the `scramble` subcommand.
-/// Loads all inputs, shuffles records, rewrites headers, and writes FASTA output.
+/// Opens all inputs and the output, then delegates to [`scramble_streams`].
 pub fn run(args: ScrambleArgs) -> Result<()> {
     if args.inputs.is_empty() {
         return Err(anyhow!("Provide at least one input file."));
     }
-    // Load all sequences (+ provenance) into memory
-    let mut all: Vec<(String, String, Vec<u8>, String)> = Vec::new(); // (orig_name, header_full, seq, file_base)
-    for path in &args.inputs {
-        let recs = read_sequences(path)
-            .with_context(|| format!("Failed to read input {}", path.display()))?;
-        let file_base = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| path.display().to_string());
-        for c in recs {
-            all.push((c.name, c.header, c.seq, file_base.clone()));
-        }
-    }
-    if all.is_empty() {
-        return Err(anyhow!("No sequences found in provided inputs."));
+    if args.inputs.iter().filter(|p| is_stdio(p)).count() > 1 {
+        return Err(anyhow!("stdin ('-') can only be given once."));
     }
 
-    // Shuffle globally
     let mut rng: StdRng = match args.seed {
         Some(s) => StdRng::seed_from_u64(s),
         None => StdRng::from_rng(thread_rng()).context("Failed to initialize RNG")?,
     };
-    all.shuffle(&mut rng);
-
-    // Build new headers: scramble_00001..N + source provenance + original header
-    let mut out: Vec<FastaRecord<'_>> = Vec::with_capacity(all.len());
-    let mut owned_headers: Vec<String> = Vec::with_capacity(all.len());
-    let mut owned_seqs: Vec<Vec<u8>> = Vec::with_capacity(all.len());
-
-    for (i, (orig_name, header_full, seq, file_base)) in all.into_iter().enumerate() {
-        let new_name = format!("scramble_{:05}", i + 1);
-        let hdr = format!("{} src={} file={} | {}", new_name, orig_name, file_base, header_full);
-        owned_headers.push(hdr);
-        owned_seqs.push(seq);
-    }
 
-    for i in 0..owned_headers.len() {
-        let hdr = owned_headers[i].clone();
-        let seq_ref = owned_seqs[i].as_slice();
-        out.push(FastaRecord { header: hdr, seq: seq_ref });
-    }
+    let tmp_parent: PathBuf = match &args.tmp_dir {
+        Some(d) => d.clone(),
+        None if is_stdio(&args.output) => std::env::temp_dir(),
+        None => match args.output.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from("."),
+        },
+    };
+
+    let mode = if args.keep_case { SeqMode::Preserve } else { SeqMode::Normalize };
+
+    // Open every input up front so the output format is known before scattering
+    let inputs = args
+        .inputs
+        .iter()
+        .map(|path| {
+            let reader = RecordReader::from_path(path)
+                .with_context(|| format!("Failed to read input {}", path.display()))?
+                .with_mode(mode);
+            Ok((source_name(path), reader))
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    write_fasta(&out, &args.output, 80)?;
-    eprintln!("Wrote {} sequences to {}", out.len(), args.output.display());
+    let buckets = bucket_count(&args.inputs)?;
+    let mut out = create_output_with(&args.output, args.compress)?;
+    let summary = scramble_streams(inputs, &tmp_parent, buckets, &mut out, &mut rng)?;
+    out.close()?;
+
+    eprintln!("Wrote {} sequences to {}", summary.records, args.output.display());
     Ok(())
 }
+
+fn source_name(path: &Path) -> String {
+    if is_stdio(path) {
+        return "stdin".to_string();
+    }
+    path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::tempdir;
+
+    #[test]
+    fn streams_keep_every_record_once() {
+        let dir = tempdir().unwrap();
+        let a = RecordReader::new(Cursor::new(">a1 x\nACGT\n>a2\nGG\n")).unwrap();
+        let b = RecordReader::new(Cursor::new(">b1\nTTT\n")).unwrap();
+        let inputs = vec![("a.fa".to_string(), a), ("b.fa".to_string(), b)];
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut out = Vec::new();
+        let summary = scramble_streams(inputs, dir.path(), 3, &mut out, &mut rng).unwrap();
+        assert_eq!(summary.records, 3);
+        assert_eq!(summary.format, Format::Fasta);
+
+        let recs: Vec<Contig> = RecordReader::new(Cursor::new(out)).unwrap().collect::<Result<_>>().unwrap();
+        let names: Vec<&str> = recs.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["scramble_00001", "scramble_00002", "scramble_00003"]);
+        let mut sources: Vec<&str> = recs.iter().map(|c| c.header.split(' ').nth(1).unwrap()).collect();
+        sources.sort();
+        assert_eq!(sources, ["src=a1", "src=a2", "src=b1"]);
+        assert!(recs.iter().any(|c| c.header.ends_with("file=a.fa | a1 x")));
+    }
+}
@@ -1,11 +1,94 @@
 //! Random interval sampler (`seq_sample`).
 //!
-//! Samples *n* sequences from random coordinates across a reference genome supplied as FASTA.
-//! Lengths are chosen uniformly in `[min, max]`. Contigs are selected **weighted by the number of valid start positions**,
-//! yielding an approximately uniform sampling over the reference coordinate space.
+//! Samples *n* sequences (or enough for a target coverage) from random coordinates across a reference genome
+//! supplied as FASTA.
+//! Lengths are chosen uniformly in `[min, max]` by default, or from a distribution given with `--length-dist`
+//! (normal, log-normal, gamma, exponential, or an empirical histogram; see [`crate::length_dist`]) truncated to
+//! `[min, max]`. Contigs (or allowed regions) are selected **weighted by the number of valid start positions**,
+//! yielding an approximately uniform sampling over the reference coordinate space. Lengths are precomputed once, so
+//! each pick is a single draw plus binary searches rather than a scan over all contigs.
 //!
-//! ### N content filter
-//! Candidate fragments containing runs of `N` with length > 2 are **rejected**.
+//! ### Coverage targets
+//! `--coverage 30x` or `--bases 1G` replace `--n`: the fragment count is the target number of bases (fold-coverage
+//! times the reference, or allowed-region, length) divided by the mean of the truncated length distribution
+//! ([`LengthDist::mean`]). The mean is taken once `--max` is clipped to the longest window the reference holds (see
+//! the ambiguity filter below), so a `--max` past the contig lengths does not undershoot the target.
+//!
+//! ### Strand
+//! Each fragment is taken from the forward strand with probability `--forward-fraction` (default 0.5) and from the
+//! minus strand otherwise. Minus-strand fragments are reverse-complemented (IUPAC-aware, see
+//! [`crate::seqio::reverse_complement`]); headers carry `strand=+` or `strand=-`, while `range=` always gives
+//! forward-strand coordinates.
+//!
+//! ### Target and excluded regions
+//! `--include-bed` restricts fragments to BED intervals and `--exclude-bed` removes intervals from the sampling
+//! space (see [`allowed_segments`]). Each fragment lies entirely within one allowed segment, and segments are
+//! weighted by their valid start positions, so sampling stays uniform over the allowed space.
+//!
+//! ### Circular contigs
+//! Contigs named with `--circular`, or with `topology=circular` in their header, are circular: every position is a
+//! valid start and fragments may span the origin. Their headers add `topology=circular`, and `range=` then counts
+//! past the contig end for wrapping fragments (`range=4991..5010` on a 5,000 bp contig covers 4991–5000 and 1–10).
+//!
+//! ### Variants
+//! With `--vcf`, fragments are sampled from haplotypes carrying the VCF's alleles (one haplotype per `GT` slot, see
+//! [`crate::vcf`]), each weighted by its length. Headers keep reference coordinates (`range=`) and add `hap=<n>` and
+//! `vars=<labels>` for the variants the fragment overlaps; `len=` is the fragment length, which differs from the
+//! `range=` span across indels.
+//!
+//! ### Transcripts (RNA-seq)
+//! With `--annotation genes.gtf` (GTF or GFF3, see [`crate::annotation`]) fragments come from spliced transcripts
+//! instead of the genome. Each transcript's expression is read from `--tpm` (a TSV of transcript ID and TPM; missing
+//! transcripts get 0) or drawn from a log-normal profile (`--expression-sigma`), and fragments are sampled in
+//! proportion to TPM × transcript length among the transcripts long enough for the drawn length. `--forward-fraction`
+//! is then the fraction of fragments on the transcript's strand. Headers add `transcript=<id> gene=<id>` and the
+//! genomic blocks of the fragment (`blocks=101..150,301..350`, 1-based inclusive); `range=` spans the blocks, introns
+//! included. `--expression-out` writes the expression used, which is the truth for a random profile.
+//!
+//! ### Truth files
+//! `--truth-bed` writes every emitted fragment as a BED6 line (0-based, half-open; name = fragment name, score `0`,
+//! strand) and `--truth-tsv` as a TSV line (`name contig start end length strand`, 1-based inclusive like the
+//! headers), so evaluation scripts need not parse headers. In paired mode each line describes the insert. A fragment
+//! spanning the origin of a circular contig takes two BED lines, and a spliced transcript fragment one per block.
+//!
+//! ### PCR duplicates and UMIs
+//! With `--duplicates geometric:MEAN` or `negbin:MEAN,SIZE` (see [`crate::duplicates`]) every sampled fragment is a
+//! molecule copied into a family of reads; `--n`, `--coverage` and `--bases` then count molecules. Each molecule gets
+//! a random UMI of `--umi-length` bases, and every read carries its own copy with substitution errors at
+//! `--umi-error-rate`, in the header as `umi=<bases>`. Reads are shuffled so families are not adjacent, and
+//! `--families-out` maps every read to its molecule (`read molecule family_size umi observed_umi`).
+//!
+//! ### Indexed references
+//! Plain and BGZF-compressed FASTA references are read through a samtools-compatible `.fai` index (one saved by
+//! `limpet faidx`, or else one built in memory; see [`crate::faidx`]). Contigs are then weighted from their indexed
+//! lengths and each fragment is fetched on demand, so memory stays flat even for a 3 Gbp assembly. Other inputs
+//! (stdin, FASTQ, plain gzip, zstd, ...) are loaded into memory.
+//!
+//! ### Ambiguity filter
+//! Fragments never contain a run of more than `--max-n-run` `N` (default 2; `n` counts too) or a symbol outside
+//! `--allowed-symbols`. The allowed regions are scanned once before sampling and split around such runs, keeping the
+//! outer `--max-n-run` bases of an `N` run, so no draw is wasted on them. If no window of `--min` bp is left the run
+//! fails up front; if the longest one is shorter than `--max`, lengths are drawn from `[--min, longest]` and the
+//! summary says so. Candidates with more than `--max-ambig-frac` non-ACGT symbols are **rejected** and counted per
+//! contig; 100,000 such rejections in a row stop the run with an error.
+//!
+//! ### Read simulation
+//! With `--error-model illumina|long-read` each fragment is turned into a **FASTQ read** with Phred qualities and
+//! substitution, insertion and deletion errors (see [`crate::simulate`]). The header keeps the true fragment
+//! (`src=`, `range=`) and adds the error counts (`subs= ins= dels=`); `--errors-out` writes every error, with its
+//! reference and read position, to a TSV sidecar.
+//!
+//! ### Paired-end reads
+//! With `--paired`, each sampled fragment is an **insert**: R1 (`--output`) reads `--read-length` bases from its
+//! start on the forward strand and R2 (`--output2`) from its end on the reverse strand. Insert sizes can follow a
+//! normal distribution (`--insert-mean`, `--insert-sd`) truncated to `[min, max]`. Reads are named `seq000001/1`
+//! and `/2`, or Casava style with `--read-names casava`; both mates carry the insert (`range=`) and the
+//! coordinates of each mate (`r1=`, `r2=`).
+//!
+//! ### Soft-masking
+//! With `--keep-case` the reference is read in [`SeqMode::Preserve`], so lowercase (soft-masked) bases are carried
+//! into the fragments unchanged. Gap symbols (`-`, `*`) are reference positions either way, as in the `.fai` index,
+//! and count as ambiguous for the filter.
 //!
 //! ### Example
 //! ```text
@@ -20,22 +103,42 @@
 //!
 //! Output headers include `src=<contig>` and 1‑based inclusive coordinates: `range=10001..10120`.
 
-use crate::seqio::{read_sequences as read_fasta, write_fasta, Contig, FastaRecord};
+use crate::annotation::{lognormal_tpm, read_annotation, read_tpm, Transcript, TranscriptTable};
+use crate::bed::{merge_by_chrom, read_bed, subtract, BedRecord};
+use crate::duplicates::{add_duplicates, FamilyRead, FamilySize};
+use crate::faidx::Reference;
+use crate::fragment::{fragment_name, write_pairs, write_reads, Fragment, Origin, ReadOutput};
+use crate::length_dist::LengthDist;
+use crate::seqio::{
+    create_output, create_output_with, reverse_complement, Codec, Contig, FastaWriter, SeqMode,
+};
+use crate::simulate::{ErrorModel, ErrorProfile, PairNaming};
+use crate::vcf::{build_haplotypes, read_vcf, Haplotype};
 use anyhow::{anyhow, Context, Result};
 use clap::Args;
 use rand::prelude::*;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 /// Arguments for `limpet seq_sample`
 #[derive(Args, Debug, Clone)]
 pub struct SeqSampleArgs {
-    /// Reference (FASTA/FASTA.GZ/FASTQ/FASTQ.GZ)
+    /// Reference (FASTA/FASTA.GZ/FASTQ/FASTQ.GZ); `-` reads stdin
     #[arg(short = 'r', long = "reference", value_name = "INPUT")]
     pub reference: PathBuf,
 
     /// Number of sequences to sample
-    #[arg(short = 'n', long = "n", value_name = "INT")]
-    pub n: usize,
+    #[arg(short = 'n', long = "n", value_name = "INT", required_unless_present_any = ["coverage", "bases"])]
+    pub n: Option<usize>,
+
+    /// Sample enough fragments for this fold-coverage of the reference (or allowed regions), e.g. `30x`
+    #[arg(long = "coverage", value_name = "FOLD", value_parser = parse_coverage, conflicts_with_all = ["n", "bases"])]
+    pub coverage: Option<f64>,
+
+    /// Sample enough fragments for this many bases in total, e.g. `1G`, `500M`, `2.5k`
+    #[arg(long = "bases", value_name = "BASES", value_parser = parse_bases, conflicts_with = "n")]
+    pub bases: Option<f64>,
 
     /// Minimum length (inclusive)
     #[arg(long = "min", value_name = "INT")]
@@ -45,19 +148,178 @@ pub struct SeqSampleArgs {
     #[arg(long = "max", value_name = "INT")]
     pub max: usize,
 
-    /// Output FASTA path
-    #[arg(short = 'o', long = "output", value_name = "FASTA")]
+    /// Output path, FASTA (FASTQ with `--error-model`); `.gz` to compress; `-` for stdout
+    #[arg(short = 'o', long = "output", value_name = "OUTPUT")]
     pub output: PathBuf,
 
     /// Optional RNG seed for reproducibility
     #[arg(long = "seed", value_name = "INT")]
     pub seed: Option<u64>,
+
+    /// Output compression (default: from the output file extension; plain for stdout)
+    #[arg(long = "compress", value_enum, value_name = "CODEC")]
+    pub compress: Option<Codec>,
+
+    /// Keep original case (soft-masking) instead of upper-casing; gap symbols such as `-` are kept either way
+    #[arg(long = "keep-case")]
+    pub keep_case: bool,
+
+    /// Simulate sequencing errors and qualities with this profile, writing FASTQ reads
+    #[arg(long = "error-model", value_enum, value_name = "PROFILE")]
+    pub error_model: Option<ErrorProfile>,
+
+    /// Mean per-base error rate (default: 0.005 for illumina, 0.08 for long-read)
+    #[arg(long = "error-rate", value_name = "FLOAT", requires = "error_model")]
+    pub error_rate: Option<f64>,
+
+    /// Write every simulated error to this TSV (read, contig, ref_pos, read_pos, type, ref, alt)
+    #[arg(long = "errors-out", value_name = "TSV", requires = "error_model")]
+    pub errors_out: Option<PathBuf>,
+
+    /// Paired-end mode: write R1 to `--output` and R2 to `--output2` (FASTQ)
+    #[arg(long = "paired", requires_all = ["output2", "read_length"])]
+    pub paired: bool,
+
+    /// R2 output path in paired mode
+    #[arg(long = "output2", value_name = "OUTPUT", requires = "paired")]
+    pub output2: Option<PathBuf>,
+
+    /// Bases read from each end of the insert in paired mode
+    #[arg(long = "read-length", value_name = "INT", requires = "paired")]
+    pub read_length: Option<usize>,
+
+    /// Read-name style in paired mode: `slash` (`/1`, `/2`) or `casava` (`1:N:0:1`)
+    #[arg(long = "read-names", value_enum, value_name = "STYLE", default_value = "slash")]
+    pub read_names: PairNaming,
+
+    /// Fragment length distribution, truncated to [--min, --max]: uniform, normal:MEAN,SD, lognormal:MEANLOG,SDLOG,
+    /// gamma:SHAPE,SCALE, exponential:MEAN or empirical:FILE (TSV of length, count)
+    #[arg(long = "length-dist", value_name = "SPEC")]
+    pub length_dist: Option<String>,
+
+    /// Mean insert (fragment) size: shorthand for `--length-dist normal:MEAN,SD`
+    #[arg(long = "insert-mean", value_name = "FLOAT", requires = "insert_sd", conflicts_with = "length_dist")]
+    pub insert_mean: Option<f64>,
+
+    /// Standard deviation of the insert size
+    #[arg(long = "insert-sd", value_name = "FLOAT", requires = "insert_mean")]
+    pub insert_sd: Option<f64>,
+
+    /// Probability that a fragment comes from the forward strand (the rest are reverse-complemented)
+    #[arg(long = "forward-fraction", value_name = "FLOAT", default_value_t = 0.5)]
+    pub forward_fraction: f64,
+
+    /// Write the true coordinates of every fragment as BED6 (0-based, half-open; name = fragment, strand)
+    #[arg(long = "truth-bed", value_name = "BED")]
+    pub truth_bed: Option<PathBuf>,
+
+    /// Write the true coordinates of every fragment as TSV (1-based inclusive, like the headers)
+    #[arg(long = "truth-tsv", value_name = "TSV")]
+    pub truth_tsv: Option<PathBuf>,
+
+    /// Sample only inside these BED intervals (repeatable; the union is used)
+    #[arg(long = "include-bed", value_name = "BED")]
+    pub include_bed: Vec<PathBuf>,
+
+    /// Never sample fragments overlapping these BED intervals (repeatable)
+    #[arg(long = "exclude-bed", value_name = "BED")]
+    pub exclude_bed: Vec<PathBuf>,
+
+    /// Never sample fragments with a run of more than this many `N`
+    #[arg(long = "max-n-run", value_name = "INT", default_value_t = 2)]
+    pub max_n_run: usize,
+
+    /// Reject fragments whose fraction of ambiguous (non-ACGT) symbols exceeds this
+    #[arg(long = "max-ambig-frac", value_name = "FLOAT", default_value_t = 1.0)]
+    pub max_ambig_frac: f64,
+
+    /// Never sample fragments containing a symbol not in this set (case-insensitive), e.g. `ACGTN`
+    #[arg(long = "allowed-symbols", value_name = "SYMBOLS")]
+    pub allowed_symbols: Option<String>,
+
+    /// Treat these contigs as circular (comma-separated or repeated); headers with `topology=circular` are too
+    #[arg(long = "circular", value_name = "NAMES", value_delimiter = ',')]
+    pub circular: Vec<String>,
+
+    /// Sample from haplotypes carrying the SNPs and indels of this VCF (one haplotype per GT allele)
+    #[arg(long = "vcf", value_name = "VCF")]
+    pub vcf: Option<PathBuf>,
+
+    /// VCF sample whose genotypes are applied (default: the first sample)
+    #[arg(long = "vcf-sample", value_name = "NAME", requires = "vcf")]
+    pub vcf_sample: Option<String>,
+
+    /// Sample RNA-seq fragments from the spliced transcripts of this GTF/GFF3 annotation
+    #[arg(long = "annotation", value_name = "GTF", conflicts_with_all = ["vcf", "include_bed", "exclude_bed", "circular"])]
+    pub annotation: Option<PathBuf>,
+
+    /// Transcript expression as a TSV of transcript ID and TPM (default: random log-normal)
+    #[arg(long = "tpm", value_name = "TSV", requires = "annotation")]
+    pub tpm: Option<PathBuf>,
+
+    /// Log-scale standard deviation of the random expression profile used without `--tpm`
+    #[arg(long = "expression-sigma", value_name = "FLOAT", default_value_t = 1.0, conflicts_with = "tpm")]
+    pub expression_sigma: f64,
+
+    /// Write the expression used (transcript, gene, length, TPM) to this TSV
+    #[arg(long = "expression-out", value_name = "TSV", requires = "annotation")]
+    pub expression_out: Option<PathBuf>,
+
+    /// Copy every fragment into a PCR duplicate family of reads: geometric:MEAN or negbin:MEAN,SIZE
+    #[arg(long = "duplicates", value_name = "SPEC")]
+    pub duplicates: Option<String>,
+
+    /// Length of the random UMI given to each molecule
+    #[arg(long = "umi-length", value_name = "INT", default_value_t = 8, requires = "duplicates")]
+    pub umi_length: usize,
+
+    /// Per-base substitution rate of the UMI in each read
+    #[arg(long = "umi-error-rate", value_name = "FLOAT", default_value_t = 0.0, requires = "duplicates")]
+    pub umi_error_rate: f64,
+
+    /// Write every read's molecule, family size and true and observed UMI to this TSV
+    #[arg(long = "families-out", value_name = "TSV", requires = "duplicates")]
+    pub families_out: Option<PathBuf>,
+}
+
+/// Parse a `--coverage` value: `30x`, `30X` or `30`.
+fn parse_coverage(s: &str) -> std::result::Result<f64, String> {
+    let t = s.trim();
+    let num = t.strip_suffix(['x', 'X']).unwrap_or(t);
+    match num.parse::<f64>() {
+        Ok(c) if c.is_finite() && c > 0.0 => Ok(c),
+        _ => Err(format!("invalid coverage '{}' (expected e.g. 30x)", s)),
+    }
+}
+
+/// Parse a `--bases` value with an optional SI suffix (`k`, `M`, `G`, `T`) and optional `b`/`bp`: `1G`, `1.5Mbp`.
+fn parse_bases(s: &str) -> std::result::Result<f64, String> {
+    let t = s.trim().to_ascii_lowercase();
+    let t = t.strip_suffix("bp").or_else(|| t.strip_suffix('b')).unwrap_or(&t);
+    let (num, scale) = match t.char_indices().last() {
+        Some((i, 'k')) => (&t[..i], 1e3),
+        Some((i, 'm')) => (&t[..i], 1e6),
+        Some((i, 'g')) => (&t[..i], 1e9),
+        Some((i, 't')) => (&t[..i], 1e12),
+        _ => (t, 1.0),
+    };
+    match num.parse::<f64>() {
+        Ok(b) if b.is_finite() && b > 0.0 => Ok(b * scale),
+        _ => Err(format!("invalid number of bases '{}' (expected e.g. 1G, 500M or 25000)", s)),
+    }
 }
 
+/// Candidates over `--max-ambig-frac` rejected in a row before the run gives up. The other filters are applied up
+/// front (see [`AmbiguousRun`]), so only this one is left to rejection.
+const MAX_CONSECUTIVE_REJECTS: usize = 100_000;
+
+/// Bases fetched at a time while scanning segments for ambiguous runs.
+const SCAN_CHUNK: usize = 1 << 20;
+
 fn has_long_n_run(seq: &[u8], max_run: usize) -> bool {
     let mut run = 0usize;
     for &b in seq {
-        if b == b'N' {
+        if b == b'N' || b == b'n' {
             run += 1;
             if run > max_run {
                 return true;
@@ -69,23 +331,729 @@ fn has_long_n_run(seq: &[u8], max_run: usize) -> bool {
     false
 }
 
-/// Execute the `seq_sample` subcommand.
+/// Sampling parameters for [`sample_fragments`].
+#[derive(Debug, Clone)]
+pub struct SeqSampleParams {
+    /// Number of fragments to produce
+    pub n: usize,
+    /// Total bases to produce instead of `n` fragments: the count is this over the mean fragment length, once `max`
+    /// is clipped to the longest window that passes the ambiguity filter
+    pub bases: Option<f64>,
+    /// Minimum fragment length (inclusive)
+    pub min: usize,
+    /// Maximum fragment length (inclusive)
+    pub max: usize,
+    /// Distribution of fragment lengths, truncated to `[min, max]`
+    pub length: LengthDist,
+    /// Probability that a fragment is taken from the forward strand
+    pub forward_fraction: f64,
+    /// Restrict fragments to these intervals (`None`: whole contigs)
+    pub include: Option<Vec<BedRecord>>,
+    /// Fragments never overlap these intervals
+    pub exclude: Vec<BedRecord>,
+    /// Fragments never contain a run of more than this many `N`
+    pub max_n_run: usize,
+    /// Reject fragments with a larger fraction of ambiguous (non-ACGT) symbols
+    pub max_ambig_frac: f64,
+    /// Fragments never contain symbols outside this set (upper case; `None`: any symbol)
+    pub allowed_symbols: Option<Vec<u8>>,
+    /// Names of circular contigs, whose fragments may span the origin
+    pub circular: Vec<String>,
+}
+
+impl Default for SeqSampleParams {
+    fn default() -> Self {
+        SeqSampleParams {
+            n: 0,
+            bases: None,
+            min: 0,
+            max: 0,
+            length: LengthDist::Uniform,
+            forward_fraction: 0.5,
+            include: None,
+            exclude: Vec::new(),
+            max_n_run: 2,
+            max_ambig_frac: 1.0,
+            allowed_symbols: None,
+            circular: Vec::new(),
+        }
+    }
+}
+
+impl SeqSampleParams {
+    fn validate(&self) -> Result<()> {
+        if self.bases.is_none() && self.n == 0 {
+            return Err(anyhow!("--n must be greater than 0"));
+        }
+        if self.bases.is_some_and(|b| !(b.is_finite() && b > 0.0)) {
+            return Err(anyhow!("the target number of bases must be positive"));
+        }
+        if self.min == 0 {
+            return Err(anyhow!("--min must be greater than 0"));
+        }
+        if self.min > self.max {
+            return Err(anyhow!("--min must be <= --max"));
+        }
+        self.length.check(self.min, self.max)?;
+        if !(0.0..=1.0).contains(&self.forward_fraction) {
+            return Err(anyhow!("--forward-fraction must be in [0, 1]"));
+        }
+        if !(0.0..=1.0).contains(&self.max_ambig_frac) {
+            return Err(anyhow!("--max-ambig-frac must be in [0, 1]"));
+        }
+        if self.allowed_symbols.as_ref().is_some_and(|s| s.is_empty()) {
+            return Err(anyhow!("--allowed-symbols must not be empty"));
+        }
+        Ok(())
+    }
+
+    /// Number of fragments to produce when lengths are drawn from `[min, max]`: `n`, or enough for `bases`.
+    fn count(&self, max: usize) -> Result<usize> {
+        let Some(bases) = self.bases else { return Ok(self.n) };
+        let n = (bases / self.length.mean(self.min, max)?).ceil();
+        if !(1.0..=usize::MAX as f64).contains(&n) {
+            return Err(anyhow!("Target of {} bases gives an unusable fragment count ({})", bases, n));
+        }
+        Ok(n as usize)
+    }
+
+    /// Flag the contigs listed in `circular`, in the order of `names`.
+    fn circular_flags(&self, names: &[&str]) -> Vec<bool> {
+        let circular: HashSet<&str> = self.circular.iter().map(String::as_str).collect();
+        names.iter().map(|n| circular.contains(n)).collect()
+    }
+
+    /// True if `seq` has no run of more than `max_n_run` `N` and no symbol outside `allowed_symbols`.
+    fn avoids_runs(&self, seq: &[u8]) -> bool {
+        if has_long_n_run(seq, self.max_n_run) {
+            return false;
+        }
+        match &self.allowed_symbols {
+            Some(allowed) => seq.iter().all(|b| allowed.contains(&b.to_ascii_uppercase())),
+            None => true,
+        }
+    }
+
+    /// True if at most `max_ambig_frac` of `seq` is ambiguous (non-ACGT).
+    fn within_ambig_frac(&self, seq: &[u8]) -> bool {
+        if self.max_ambig_frac >= 1.0 {
+            return true;
+        }
+        let ambig = seq.iter().filter(|b| !matches!(b.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T' | b'U')).count();
+        ambig as f64 <= self.max_ambig_frac * seq.len() as f64
+    }
+
+    /// Where accepted windows must stop and may resume around `runs`, as `(cut, resume)` pairs: a window overlapping
+    /// a run ends by `cut` or starts at `resume` or later. A run of more than `max_n_run` `N` keeps its outer
+    /// `max_n_run` bases usable, a run of disallowed symbols none. Also returns the bases in those runs.
+    ///
+    /// `resume` never precedes `cut`, so every start belongs to one piece and windows stay uniform; windows that
+    /// start inside an `N` run shorter than twice `max_n_run` and leave it past `cut` are not drawn.
+    fn cuts(&self, runs: &[AmbiguousRun]) -> (Vec<(usize, usize)>, usize) {
+        let mut cuts = Vec::new();
+        let mut bases = 0;
+        for run in runs {
+            if run.disallowed {
+                cuts.push((run.start, run.end));
+            } else if run.end - run.start > self.max_n_run {
+                let cut = run.start + self.max_n_run;
+                cuts.push((cut, (run.end - self.max_n_run).max(cut)));
+            } else {
+                continue;
+            }
+            bases += run.end - run.start;
+        }
+        (cuts, bases)
+    }
+}
+
+/// What the ambiguity filter and the length bounds did during sampling, per contig (see [`sample_reference`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SampleStats {
+    /// Bases in runs of more than `max_n_run` `N` or of symbols outside `allowed_symbols`, left out of sampling
+    pub excluded: Vec<usize>,
+    /// Candidates rejected by the ambiguity filter
+    pub rejected: Vec<usize>,
+    /// Longest fragment any allowed region (or expressed transcript) free of ambiguous runs can hold, when shorter
+    /// than `max`: lengths are then drawn from `[min, longest]`
+    pub longest: Option<usize>,
+}
+
+impl SampleStats {
+    fn new(contigs: usize) -> Self {
+        SampleStats { excluded: vec![0; contigs], rejected: vec![0; contigs], longest: None }
+    }
+}
+
+/// A stretch of a contig that fragments may be drawn from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    /// Index of the contig
+    pub contig: usize,
+    /// 0-based start
+    pub start: usize,
+    /// 0-based exclusive end; past the contig length for a circular contig's segment that crosses the origin
+    pub end: usize,
+    /// The whole of a circular contig: fragments may start anywhere and wrap around
+    pub circular: bool,
+}
+
+impl Segment {
+    /// Length in bp.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// True if the segment is empty.
+    pub fn is_empty(&self) -> bool {
+        self.end == self.start
+    }
+}
+
+/// Allowed segments of contigs with the given `names` and `lengths`: the `include` intervals (or whole contigs)
+/// minus the `exclude` intervals. Intervals on unknown contigs are ignored and intervals are clipped to the contig.
+///
+/// On contigs flagged in `circular`, an unmasked contig becomes one circular segment, and allowed intervals touching
+/// both ends are joined across the origin.
+pub fn allowed_segments(
+    names: &[&str],
+    lengths: &[usize],
+    circular: &[bool],
+    include: Option<&[BedRecord]>,
+    exclude: &[BedRecord],
+) -> Vec<Segment> {
+    let include = include.map(merge_by_chrom);
+    let exclude = merge_by_chrom(exclude);
+    let mut out = Vec::new();
+    for (i, (&name, &len)) in names.iter().zip(lengths).enumerate() {
+        let len = len as u64;
+        let keep: Vec<(u64, u64)> = match &include {
+            Some(inc) => inc.get(name).map_or_else(Vec::new, |v| {
+                v.iter().filter(|&&(s, _)| s < len).map(|&(s, e)| (s, e.min(len))).collect()
+            }),
+            None => vec![(0, len)],
+        };
+        let mut keep = match exclude.get(name) {
+            Some(ex) => subtract(&keep, ex),
+            None => keep,
+        };
+        if circular.get(i).copied().unwrap_or(false) && len > 0 {
+            if keep == [(0, len)] {
+                out.push(Segment { contig: i, start: 0, end: len as usize, circular: true });
+                continue;
+            }
+            if keep.len() > 1 && keep[0].0 == 0 && keep[keep.len() - 1].1 == len {
+                let (_, head_end) = keep.remove(0);
+                keep.last_mut().unwrap().1 = len + head_end;
+            }
+        }
+        out.extend(keep.into_iter().map(|(s, e)| Segment { contig: i, start: s as usize, end: e as usize, circular: false }));
+    }
+    out
+}
+
+/// Segments of one kind, longest first, with cumulative lengths `S(k)` of the first `k` segments.
+struct StartGroup {
+    segments: Vec<Segment>,
+    cumulative: Vec<u64>,
+    /// Whole circular contigs: every position is a valid start
+    circular: bool,
+}
+
+impl StartGroup {
+    fn new(mut segments: Vec<Segment>, circular: bool) -> Self {
+        segments.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        let mut cumulative = Vec::with_capacity(segments.len() + 1);
+        cumulative.push(0u64);
+        for s in &segments {
+            cumulative.push(cumulative.last().unwrap() + s.len() as u64);
+        }
+        StartGroup { segments, cumulative, circular }
+    }
+
+    /// Number of segments at least `len` long (a prefix).
+    fn fitting(&self, len: usize) -> usize {
+        self.segments.partition_point(|s| s.len() >= len)
+    }
+
+    /// Valid starts for length `len` in the first `k` segments: `S(k) - k(L - 1)`, or `S(k)` for circles.
+    fn starts(&self, k: usize, len: usize) -> u64 {
+        if self.circular { self.cumulative[k] } else { self.cumulative[k] - k as u64 * (len as u64 - 1) }
+    }
+
+    /// Map the `u`-th valid start among the first `k` segments to `(contig, start)`.
+    fn locate(&self, k: usize, len: usize, u: u64) -> (usize, usize) {
+        // Binary search for the first segment j with starts(j + 1) > u
+        let (mut lo, mut hi) = (0, k - 1);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.starts(mid + 1, len) > u { hi = mid; } else { lo = mid + 1; }
+        }
+        let seg = &self.segments[lo];
+        (seg.contig, seg.start + (u - self.starts(lo, len)) as usize)
+    }
+}
+
+/// Picks a uniformly random valid start position for a fragment length across all segments with one draw.
+///
+/// Segments are sorted by decreasing length with cumulative lengths `S(k)` precomputed once. For a length `L`, the
+/// segments that fit are a prefix of `k` segments (found by binary search) holding `C(k) = S(k) - k(L - 1)` valid
+/// starts; a single draw in `0..C(k)` is mapped back to its segment by a second binary search. This is the same
+/// distribution as weighting each segment by `len - L + 1` and then drawing a start uniformly within it. Whole
+/// circular contigs are kept apart, as each of their positions is a valid start for any `L` up to their length.
+struct StartTable {
+    linear: StartGroup,
+    circles: StartGroup,
+}
+
+impl StartTable {
+    fn new(segments: Vec<Segment>) -> Self {
+        let (circles, linear) = segments.into_iter().partition(|s| s.circular);
+        StartTable { linear: StartGroup::new(linear, false), circles: StartGroup::new(circles, true) }
+    }
+
+    /// Valid starts for a fragment of `len` bp.
+    fn total(&self, len: usize) -> u64 {
+        self.linear.starts(self.linear.fitting(len), len) + self.circles.starts(self.circles.fitting(len), len)
+    }
+
+    /// The `u`-th valid start (`u < total(len)`) as `(contig, start)`; `start` may lie past the end of a circular
+    /// contig for a segment joined across its origin.
+    fn nth(&self, len: usize, u: u64) -> (usize, usize) {
+        let k = self.linear.fitting(len);
+        let linear = self.linear.starts(k, len);
+        if u < linear {
+            self.linear.locate(k, len, u)
+        } else {
+            self.circles.locate(self.circles.fitting(len), len, u - linear)
+        }
+    }
+
+    /// Draw `(contig, start)` for a fragment of `len` bp, or `None` if no segment is long enough.
+    fn pick<R: Rng>(&self, len: usize, rng: &mut R) -> Option<(usize, usize)> {
+        match self.total(len) {
+            0 => None,
+            total => Some(self.nth(len, rng.gen_range(0..total))),
+        }
+    }
+}
+
+/// A maximal run of `N` (`disallowed: false`) or of symbols outside `--allowed-symbols` (`disallowed: true`).
 ///
-/// Returns an error if the input lacks contigs ≥ `min`, or if
+/// Every window containing more than `--max-n-run` bases of an `N` run, or any base of a disallowed run, fails the
+/// ambiguity filter whatever its length, so segments are split around these runs before sampling (see
+/// [`SeqSampleParams::cuts`]) rather than rejecting candidates one by one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AmbiguousRun {
+    start: usize,
+    end: usize,
+    disallowed: bool,
+}
+
+/// Collects the [`AmbiguousRun`]s of a sequence fed in consecutive chunks.
+struct RunScanner<'a> {
+    allowed: Option<&'a [u8]>,
+    runs: Vec<AmbiguousRun>,
+}
+
+impl<'a> RunScanner<'a> {
+    fn new(params: &'a SeqSampleParams) -> Self {
+        RunScanner { allowed: params.allowed_symbols.as_deref(), runs: Vec::new() }
+    }
+
+    /// Scan `seq`, which starts at position `pos`.
+    fn feed(&mut self, pos: usize, seq: &[u8]) {
+        for (p, &b) in (pos..).zip(seq) {
+            let disallowed = self.allowed.is_some_and(|a| !a.contains(&b.to_ascii_uppercase()));
+            if !disallowed && b != b'N' && b != b'n' {
+                continue;
+            }
+            match self.runs.last_mut() {
+                Some(run) if run.end == p && run.disallowed == disallowed => run.end += 1,
+                _ => self.runs.push(AmbiguousRun { start: p, end: p + 1, disallowed }),
+            }
+        }
+    }
+}
+
+/// The pieces of `start..end` between sorted `(cut, resume)` pairs.
+fn split_at_cuts(start: usize, end: usize, cuts: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut pieces = Vec::new();
+    let mut from = start;
+    for &(cut, resume) in cuts {
+        if cut > from {
+            pieces.push((from, cut));
+        }
+        from = resume;
+    }
+    if end > from {
+        pieces.push((from, end));
+    }
+    pieces
+}
+
+/// Fetch `start..end` of contig `contig` of `len` bp; positions past `len` wrap around the origin.
+fn fetch_span<F>(fetch: &mut F, contig: usize, len: usize, start: usize, end: usize) -> Result<Vec<u8>>
+where
+    F: FnMut(usize, usize, usize) -> Result<Vec<u8>>,
+{
+    if start >= len {
+        return fetch(contig, start - len, end - len);
+    }
+    if end <= len {
+        return fetch(contig, start, end);
+    }
+    let mut seq = fetch(contig, start, len)?;
+    seq.extend(fetch(contig, 0, end - len)?);
+    Ok(seq)
+}
+
+/// Scan each segment once through `fetch` and split it around its [`AmbiguousRun`]s, so that every window of the
+/// returned segments avoids them. A whole circular contig with runs becomes linear pieces, the last one joined
+/// across the origin. Also returns the bases in those runs per contig.
+fn avoid_ambiguous_runs<F>(
+    segments: Vec<Segment>,
+    lengths: &[usize],
+    params: &SeqSampleParams,
+    fetch: &mut F,
+) -> Result<(Vec<Segment>, Vec<usize>)>
+where
+    F: FnMut(usize, usize, usize) -> Result<Vec<u8>>,
+{
+    let mut out = Vec::with_capacity(segments.len());
+    let mut excluded = vec![0usize; lengths.len()];
+    for seg in segments {
+        let len = lengths[seg.contig];
+        let mut scanner = RunScanner::new(params);
+        let mut pos = seg.start;
+        while pos < seg.end {
+            let to = seg.end.min(pos + SCAN_CHUNK);
+            scanner.feed(pos, &fetch_span(fetch, seg.contig, len, pos, to)?);
+            pos = to;
+        }
+        let mut runs = scanner.runs;
+        // On a circle, runs at both ends are one run across the origin
+        if seg.circular && runs.len() > 1 {
+            let (first, last) = (runs[0], runs[runs.len() - 1]);
+            if first.start == 0 && last.end == len && first.disallowed == last.disallowed {
+                runs.remove(0);
+                runs.last_mut().unwrap().end = len + first.end;
+            }
+        }
+        let (cuts, bases) = params.cuts(&runs);
+        excluded[seg.contig] += bases;
+        let piece = |(start, end)| Segment { contig: seg.contig, start, end, circular: false };
+        if cuts.is_empty() {
+            out.push(seg);
+        } else if !seg.circular {
+            out.extend(split_at_cuts(seg.start, seg.end, &cuts).into_iter().map(piece));
+        } else if runs.len() > 1 || runs[0].end - runs[0].start < len {
+            // Pieces from each run to the next, the last one wrapping to the first run
+            for (i, &(_, resume)) in cuts.iter().enumerate() {
+                let next = if i + 1 == cuts.len() { cuts[0].0 + len } else { cuts[i + 1].0 };
+                let (start, end) = if resume >= len { (resume - len, next - len) } else { (resume, next) };
+                if end > start {
+                    out.push(piece((start, end)));
+                }
+            }
+        }
+    }
+    Ok((out, excluded))
+}
+
+/// Sample `params.n` fragments (or `params.bases` in total) from `contigs`.
+///
+/// Returns an error if no contig is at least `min` long, or if
 /// the requested parameters are inconsistent.
-pub fn run(args: SeqSampleArgs) -> Result<()> {
-    if args.n == 0 {
-        return Err(anyhow!("--n must be greater than 0"));
+pub fn sample_fragments<R: Rng>(contigs: &[Contig], params: &SeqSampleParams, rng: &mut R) -> Result<Vec<Fragment>> {
+    let names: Vec<&str> = contigs.iter().map(|c| c.name.as_str()).collect();
+    let lengths: Vec<usize> = contigs.iter().map(|c| c.seq.len()).collect();
+    let (fragments, _) = sample_by_length(&names, &lengths, params, rng, |i, start, end| Ok(contigs[i].seq[start..end].to_vec()))?;
+    Ok(fragments)
+}
+
+/// Sample `params.n` fragments (or `params.bases` in total) from a [`Reference`], fetching only the sampled intervals.
+///
+/// The allowed segments are scanned once up front for ambiguous runs, which fragments then avoid; runs, rejections
+/// by `--max-ambig-frac` and redrawn lengths are returned per contig.
+pub fn sample_reference<R: Rng>(
+    reference: &mut Reference,
+    params: &SeqSampleParams,
+    rng: &mut R,
+) -> Result<(Vec<Fragment>, SampleStats)> {
+    let names: Vec<String> = (0..reference.len()).map(|i| reference.name(i).to_string()).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let lengths: Vec<usize> = (0..reference.len()).map(|i| reference.seq_len(i)).collect();
+    sample_by_length(&names, &lengths, params, rng, |i, start, end| reference.fetch(i, start, end))
+}
+
+/// Sample `params.n` fragments (or `params.bases` in total) from VCF haplotypes of `reference` (see [`crate::vcf::build_haplotypes`]).
+///
+/// Each haplotype is sampled as a contig of its own, weighted by its length, with BED masks lifted onto it.
+/// Fragments report reference coordinates, the haplotype and the variants they carry. Statistics are summed per
+/// reference contig.
+pub fn sample_haplotypes<R: Rng>(
+    reference: &mut Reference,
+    haplotypes: &[Haplotype],
+    params: &SeqSampleParams,
+    rng: &mut R,
+) -> Result<(Vec<Fragment>, SampleStats)> {
+    let hap_name = |h: &Haplotype| format!("{}#{}", reference.name(h.contig), h.index + 1);
+    let names: Vec<String> = haplotypes.iter().map(hap_name).collect();
+    let by_name: HashMap<&str, &Haplotype> = names.iter().map(String::as_str).zip(haplotypes).collect();
+    let circular: HashSet<&str> = params.circular.iter().map(String::as_str).collect();
+    let lift = |records: &[BedRecord]| -> Vec<BedRecord> {
+        let mut out = Vec::new();
+        for (h, name) in haplotypes.iter().zip(&names) {
+            for r in records.iter().filter(|r| r.chrom == reference.name(h.contig)) {
+                let (start, end) = (h.lift(r.start as usize) as u64, h.lift(r.end as usize) as u64);
+                out.push(BedRecord { chrom: name.clone(), start, end, ..r.clone() });
+            }
+        }
+        out
+    };
+    let hap_params = SeqSampleParams {
+        include: params.include.as_deref().map(lift),
+        exclude: lift(&params.exclude),
+        circular: haplotypes.iter().zip(&names).filter(|(h, _)| circular.contains(reference.name(h.contig))).map(|(_, n)| n.clone()).collect(),
+        ..params.clone()
+    };
+
+    let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
+    let lengths: Vec<usize> = haplotypes.iter().map(Haplotype::len).collect();
+    let (fragments, hap_stats) =
+        sample_by_length(&name_refs, &lengths, &hap_params, rng, |i, start, end| haplotypes[i].fetch(reference, start, end))?;
+
+    let mut stats = SampleStats { longest: hap_stats.longest, ..SampleStats::new(reference.len()) };
+    for (i, h) in haplotypes.iter().enumerate() {
+        stats.excluded[h.contig] += hap_stats.excluded[i];
+        stats.rejected[h.contig] += hap_stats.rejected[i];
+    }
+    let out = fragments.into_iter().map(|f| by_name[f.contig.as_str()].to_reference(reference, f)).collect();
+    Ok((out, stats))
+}
+
+/// Sample `params.n` RNA-seq fragments (or `params.bases`) from spliced `transcripts` of `reference`, weighting each transcript by its
+/// `tpm` times its length. BED masks and circular contigs do not apply; expressed transcripts are scanned once and
+/// split around ambiguous runs as in [`sample_reference`].
+///
+/// Fragments report the genomic span of their exon blocks in `start`/`end` and the blocks themselves in `lift`.
+pub fn sample_transcripts<R: Rng>(
+    reference: &mut Reference,
+    transcripts: &[Transcript],
+    tpm: &[f64],
+    params: &SeqSampleParams,
+    rng: &mut R,
+) -> Result<(Vec<Fragment>, SampleStats)> {
+    params.validate()?;
+    let contigs: Vec<usize> = transcripts
+        .iter()
+        .map(|t| reference.position(&t.chrom).ok_or_else(|| anyhow!("Transcript '{}' is on unknown contig '{}'", t.id, t.chrom)))
+        .collect::<Result<_>>()?;
+    if !transcripts.iter().zip(tpm).any(|(t, &x)| x > 0.0 && t.len() >= params.min) {
+        return Err(anyhow!("No expressed transcript is at least {} bp long.", params.min));
+    }
+
+    let mut stats = SampleStats::new(reference.len());
+    let mut pieces = vec![Vec::new(); transcripts.len()];
+    for (i, t) in transcripts.iter().enumerate() {
+        if tpm[i] <= 0.0 || t.is_empty() {
+            continue;
+        }
+        let mut scanner = RunScanner::new(params);
+        let mut pos = 0;
+        for (s, e) in t.blocks(0, t.len()) {
+            scanner.feed(pos, &reference.fetch(contigs[i], s, e)?);
+            pos += e - s;
+        }
+        let (cuts, bases) = params.cuts(&scanner.runs);
+        stats.excluded[contigs[i]] += bases;
+        pieces[i] = split_at_cuts(0, t.len(), &cuts);
+    }
+    let table = TranscriptTable::new(transcripts, tpm, pieces);
+    let longest = table.longest();
+    if longest < params.min {
+        return Err(anyhow!(
+            "No window of at least {} bp of an expressed transcript passes the ambiguity filter: they have runs of more \
+             than --max-n-run ({}) N or of symbols outside --allowed-symbols (relax those options or lower --min)",
+            params.min,
+            params.max_n_run
+        ));
+    }
+    // Lengths no window can hold would only be drawn again, so the distribution is truncated to those that fit
+    let max = params.max.min(longest);
+    params.length.check(params.min, max).map_err(|e| anyhow!("{}; no expressed transcript has a stretch free of ambiguous runs longer than {} bp", e, longest))?;
+    stats.longest = (max < params.max).then_some(max);
+    let n = params.count(max)?;
+
+    let mut out: Vec<Fragment> = Vec::with_capacity(n);
+    let mut misses = 0usize;
+    while out.len() < n {
+        let len = params.length.sample(params.min, max, rng)?;
+        let (i, start) = table.pick(len, rng).expect("lengths fit the longest transcript piece");
+        let t = &transcripts[i];
+        let (seq, lift) = t.splice(start, start + len, |s, e| reference.fetch(contigs[i], s, e))?;
+
+        debug_assert!(params.avoids_runs(&seq), "window overlaps an ambiguous run");
+        if !params.within_ambig_frac(&seq) {
+            stats.rejected[contigs[i]] += 1;
+            misses += 1;
+            if misses >= MAX_CONSECUTIVE_REJECTS {
+                return Err(anyhow!(
+                    "Gave up after {} consecutive candidates with more than --max-ambig-frac ({}) ambiguous symbols \
+                     ({} of {} fragments sampled); raise --max-ambig-frac",
+                    misses,
+                    params.max_ambig_frac,
+                    out.len(),
+                    n
+                ));
+            }
+            continue;
+        }
+        misses = 0;
+
+        out.push(t.fragment(seq, lift, rng.gen_bool(params.forward_fraction)));
+    }
+    Ok((out, stats))
+}
+
+/// Core sampler: weights the allowed segments of contigs with `lengths` by their valid start positions and calls
+/// `fetch(contig, start, end)` for each candidate. The segments are first scanned and split around ambiguous runs
+/// (see [`AmbiguousRun`]), so sampling fails up front when no window can pass the filter.
+fn sample_by_length<R, F>(
+    names: &[&str],
+    lengths: &[usize],
+    params: &SeqSampleParams,
+    rng: &mut R,
+    mut fetch: F,
+) -> Result<(Vec<Fragment>, SampleStats)>
+where
+    R: Rng,
+    F: FnMut(usize, usize, usize) -> Result<Vec<u8>>,
+{
+    params.validate()?;
+    if !lengths.iter().any(|&l| l >= params.min) {
+        return Err(anyhow!("No sequences are at least {} bp long.", params.min));
+    }
+    let circular = params.circular_flags(names);
+    let segments = allowed_segments(names, lengths, &circular, params.include.as_deref(), &params.exclude);
+    if !segments.iter().any(|s| s.len() >= params.min) {
+        return Err(anyhow!("No allowed region (after --include-bed/--exclude-bed) is at least {} bp long.", params.min));
+    }
+
+    let mut stats = SampleStats::new(names.len());
+    let (segments, excluded) = avoid_ambiguous_runs(segments, lengths, params, &mut fetch)?;
+    stats.excluded = excluded;
+    let longest = segments.iter().map(Segment::len).max().unwrap_or(0);
+    if longest < params.min {
+        return Err(anyhow!(
+            "No window of at least {} bp passes the ambiguity filter: {} bp of the allowed reference are in runs of more \
+             than --max-n-run ({}) N or of symbols outside --allowed-symbols (relax those options, lower --min or mask \
+             the ambiguous regions with --exclude-bed)",
+            params.min,
+            stats.excluded.iter().sum::<usize>(),
+            params.max_n_run
+        ));
+    }
+    // Lengths no window can hold would only be drawn again, so the distribution is truncated to those that fit
+    let max = params.max.min(longest);
+    params.length.check(params.min, max).map_err(|e| anyhow!("{}; no allowed region free of ambiguous runs is longer than {} bp", e, longest))?;
+    stats.longest = (max < params.max).then_some(max);
+    let n = params.count(max)?;
+
+    let table = StartTable::new(segments);
+    let mut out: Vec<Fragment> = Vec::with_capacity(n);
+    let mut misses = 0usize;
+
+    while out.len() < n {
+        let len = params.length.sample(params.min, max, rng)?;
+        let (chosen_idx, start) = table.pick(len, rng).expect("lengths fit the longest segment");
+        let contig_len = lengths[chosen_idx];
+        // Segments joined across the origin of a circular contig can yield starts past its end
+        let start = if start >= contig_len { start - contig_len } else { start };
+        let end = start + len;
+        let seq = fetch_span(&mut fetch, chosen_idx, contig_len, start, end)?;
+
+        debug_assert!(params.avoids_runs(&seq), "window overlaps an ambiguous run");
+        if !params.within_ambig_frac(&seq) {
+            stats.rejected[chosen_idx] += 1;
+            misses += 1;
+            if misses >= MAX_CONSECUTIVE_REJECTS {
+                return Err(anyhow!(
+                    "Gave up after {} consecutive candidates with more than --max-ambig-frac ({}) ambiguous symbols \
+                     ({} of {} fragments sampled); raise --max-ambig-frac or mask ambiguous regions with --exclude-bed",
+                    misses,
+                    params.max_ambig_frac,
+                    out.len(),
+                    n
+                ));
+            }
+            continue;
+        }
+        misses = 0;
+
+        let reverse = !rng.gen_bool(params.forward_fraction);
+        let seq = if reverse { reverse_complement(&seq) } else { seq };
+        let circular_len = circular[chosen_idx].then_some(contig_len);
+        out.push(Fragment {
+            contig: names[chosen_idx].to_string(),
+            start,
+            end,
+            reverse,
+            seq,
+            circular_len,
+            origin: Origin::Genome,
+            umi: None,
+        });
     }
-    if args.min == 0 {
-        return Err(anyhow!("--min must be greater than 0"));
+    Ok((out, stats))
+}
+
+/// Execute the `seq_sample` subcommand.
+///
+/// Opens the reference (indexed when possible), samples fragments with [`sample_reference`] and writes FASTA, or
+/// FASTQ reads simulated from the fragments when an error model is selected.
+pub fn run(args: SeqSampleArgs) -> Result<()> {
+    let length = match (&args.length_dist, args.insert_mean, args.insert_sd) {
+        (Some(spec), _, _) => LengthDist::parse(spec)?,
+        (None, Some(mean), Some(sd)) => LengthDist::normal(mean, sd)?,
+        _ => LengthDist::Uniform,
+    };
+    let include = match args.include_bed.is_empty() {
+        true => None,
+        false => Some(read_beds(&args.include_bed)?),
+    };
+    let mut params = SeqSampleParams {
+        // With --coverage/--bases the target is set once the reference length is known
+        n: args.n.unwrap_or(1),
+        bases: None,
+        min: args.min,
+        max: args.max,
+        length,
+        forward_fraction: args.forward_fraction,
+        include,
+        exclude: read_beds(&args.exclude_bed)?,
+        max_n_run: args.max_n_run,
+        max_ambig_frac: args.max_ambig_frac,
+        allowed_symbols: args.allowed_symbols.as_ref().map(|s| s.trim().to_ascii_uppercase().into_bytes()),
+        circular: Vec::new(),
+    };
+    params.validate()?;
+    let family = args.duplicates.as_deref().map(FamilySize::parse).transpose()?;
+    if !(0.0..=1.0).contains(&args.umi_error_rate) {
+        return Err(anyhow!("--umi-error-rate must be in [0, 1]"));
     }
-    if args.min > args.max {
-        return Err(anyhow!("--min must be <= --max"));
+    if args.paired {
+        match args.read_length {
+            Some(0) => return Err(anyhow!("--read-length must be greater than 0")),
+            Some(rl) if rl > args.min => return Err(anyhow!("--min must be >= --read-length in paired mode")),
+            _ => {}
+        }
     }
 
-    let contigs = read_fasta(&args.reference)?;
-    if !contigs.iter().any(|c| c.seq.len() >= args.min) {
+    let mode = if args.keep_case { SeqMode::Preserve } else { SeqMode::Normalize };
+    let mut reference = Reference::open(&args.reference, mode)?;
+    if !(0..reference.len()).any(|i| reference.seq_len(i) >= args.min) {
         return Err(anyhow!(
             "No sequences in {} are at least {} bp long.",
             args.reference.display(),
@@ -93,86 +1061,351 @@ pub fn run(args: SeqSampleArgs) -> Result<()> {
         ));
     }
 
+    params.circular = circular_contigs(&mut reference, &args.circular)?;
+    let haplotypes = match &args.vcf {
+        Some(path) => Some(load_haplotypes(&mut reference, path, args.vcf_sample.as_deref())?),
+        None => None,
+    };
+
+    let model = match args.error_model {
+        Some(profile) => {
+            let model = ErrorModel::new(profile);
+            Some(match args.error_rate {
+                Some(rate) => model.with_rate(rate)?,
+                None => model,
+            })
+        }
+        None => None,
+    };
+
     let mut rng: StdRng = match args.seed {
         Some(s) => StdRng::seed_from_u64(s),
         None => StdRng::from_rng(thread_rng()).context("Failed to initialize RNG")?,
     };
 
-    let mut out_records: Vec<(String, Vec<u8>)> = Vec::with_capacity(args.n);
+    let transcripts = match &args.annotation {
+        Some(path) => Some(load_transcripts(&reference, path, &args, &mut rng)?),
+        None => None,
+    };
+
+    let allowed = if let Some((transcripts, tpm)) = &transcripts {
+        transcripts.iter().zip(tpm).filter(|(_, &x)| x > 0.0).map(|(t, _)| t.len()).sum()
+    } else if params.include.is_some() || !params.exclude.is_empty() {
+        print_mask_summary(&reference, &params)
+    } else {
+        (0..reference.len()).map(|i| reference.seq_len(i)).sum()
+    };
+    let target = match args.n {
+        Some(_) => None,
+        None => Some(target_bases(args.coverage, args.bases, allowed)?),
+    };
+    params.bases = target.as_ref().map(|(bases, _)| *bases);
+    let (fragments, stats) = match (&haplotypes, &transcripts) {
+        (Some(haps), _) => sample_haplotypes(&mut reference, haps, &params, &mut rng)?,
+        (None, Some((transcripts, tpm))) => sample_transcripts(&mut reference, transcripts, tpm, &params, &mut rng)?,
+        (None, None) => sample_reference(&mut reference, &params, &mut rng)?,
+    };
+    // Lengths are drawn up to the longest window that passes the ambiguity filter
+    let max = stats.longest.unwrap_or(params.max);
+    if let Some((_, what)) = &target {
+        let mean = params.length.mean(params.min, max)?;
+        eprintln!("Target: {} at mean fragment length {:.1} -> {} fragments", what, mean, fragments.len());
+    }
+    print_summary(&params, max, &fragments);
+    print_filter_summary(&reference, &params, &stats);
+    let fragments = match &family {
+        Some(family) => {
+            let molecules = fragments.len();
+            let (reads, families) = add_duplicates(fragments, family, args.umi_length, args.umi_error_rate, &mut rng);
+            eprintln!(
+                "Duplicates: {} molecules -> {} reads (family size {}, UMI {} bp, UMI error rate {})",
+                molecules,
+                reads.len(),
+                family,
+                args.umi_length,
+                args.umi_error_rate
+            );
+            write_families(&args, &reads, &families)?;
+            reads
+        }
+        None => fragments,
+    };
+    write_truth(&args, &fragments)?;
+
+    let out = ReadOutput {
+        output: &args.output,
+        output2: args.output2.as_deref(),
+        compress: args.compress,
+        errors_out: args.errors_out.as_deref(),
+        read_length: args.read_length,
+        read_names: args.read_names,
+    };
+    if args.paired {
+        let inserts = format!("{} in [{}, {}]", params.length, params.min, max);
+        return write_pairs(&out, &inserts, &fragments, model.as_ref(), &mut rng);
+    }
+    if let Some(model) = model {
+        return write_reads(&out, &fragments, &model, &mut rng);
+    }
 
-    while out_records.len() < args.n {
-        let len = rng.gen_range(args.min..=args.max);
+    let mut writer = FastaWriter::new(create_output_with(&args.output, args.compress)?, 80);
+    for (i, f) in fragments.iter().enumerate() {
+        writer.write_record(&f.header(i + 1), &f.seq)?;
+    }
+    writer.finish()?.close()?;
+    eprintln!("Wrote {} sequences to {}", fragments.len(), args.output.display());
+    Ok(())
+}
 
-        // Compute weights = available start positions per contig
-        let mut weights: Vec<u64> = Vec::with_capacity(contigs.len());
-        let mut total: u128 = 0;
-        for c in &contigs {
-            if c.seq.len() >= len {
-                let w = (c.seq.len() - len + 1) as u64;
-                weights.push(w);
-                total += w as u128;
-            } else {
-                weights.push(0);
-            }
+/// Names of circular contigs: those listed with `--circular` plus those with `topology=circular` in their header.
+fn circular_contigs(reference: &mut Reference, listed: &[String]) -> Result<Vec<String>> {
+    let mut out = Vec::new();
+    for name in listed {
+        match reference.position(name) {
+            Some(_) => out.push(name.clone()),
+            None => eprintln!("Warning: --circular contig '{}' is not in the reference", name),
         }
-        if total == 0 {
-            // No contig can fit this length; try another length
-            continue;
+    }
+    for i in 0..reference.len() {
+        let header = reference.header(i)?;
+        if header.split_whitespace().any(|t| t.eq_ignore_ascii_case("topology=circular")) {
+            out.push(reference.name(i).to_string());
         }
+    }
+    out.sort_unstable();
+    out.dedup();
+    if !out.is_empty() {
+        eprintln!("Circular contigs: {}", out.join(", "));
+    }
+    Ok(out)
+}
 
-        // Weighted choose contig
-        let mut pick = rng.gen_range(0..total) as u128;
-        let mut chosen_idx = 0usize;
-        for (i, &w) in weights.iter().enumerate() {
-            if w == 0 { continue; }
-            if pick < w as u128 {
-                chosen_idx = i;
-                break;
-            }
-            pick -= w as u128;
-        }
+/// Read `--vcf` and build the haplotypes to sample from, reporting what was applied.
+fn load_haplotypes(reference: &mut Reference, path: &Path, sample: Option<&str>) -> Result<Vec<Haplotype>> {
+    let vcf = read_vcf(path, sample)?;
+    let (haplotypes, left_out) = build_haplotypes(reference, &vcf)?;
+    let applied: usize = haplotypes.iter().map(Haplotype::edits).sum();
+    eprintln!(
+        "VCF {}: {} variant(s) for {} (ploidy {}), {} allele(s) applied across haplotypes",
+        path.display(),
+        vcf.variants.len(),
+        vcf.sample.as_deref().unwrap_or("a sites-only file"),
+        vcf.ploidy,
+        applied
+    );
+    if vcf.skipped > 0 || left_out > 0 {
+        eprintln!(
+            "Warning: skipped {} symbolic/breakend record(s) and {} variant(s) on unknown contigs or overlapping another",
+            vcf.skipped, left_out
+        );
+    }
+    Ok(haplotypes)
+}
 
-        let c: &Contig = &contigs[chosen_idx];
-        let max_start = c.seq.len() - len;
-        let start = rng.gen_range(0..=max_start);
-        let end = start + len;
-        let slice = &c.seq[start..end];
+/// Read `--annotation` and the transcripts' expression (`--tpm`, or a random log-normal profile), keeping the
+/// transcripts that lie on the reference. Writes `--expression-out` and reports what was loaded.
+fn load_transcripts<R: Rng>(
+    reference: &Reference,
+    path: &Path,
+    args: &SeqSampleArgs,
+    rng: &mut R,
+) -> Result<(Vec<Transcript>, Vec<f64>)> {
+    let all = read_annotation(path)?;
+    let total = all.len();
+    let fits = |t: &Transcript| {
+        let last = t.exons.last().map_or(0, |e| e.1);
+        reference.position(&t.chrom).is_some_and(|i| last <= reference.seq_len(i))
+    };
+    let transcripts: Vec<Transcript> = all.into_iter().filter(fits).collect();
+    if transcripts.is_empty() {
+        return Err(anyhow!("No transcripts in {} lie on the reference", path.display()));
+    }
+    let genes: HashSet<&str> = transcripts.iter().map(|t| t.gene.as_str()).collect();
+    eprintln!("Annotation {}: {} transcript(s) of {} gene(s)", path.display(), transcripts.len(), genes.len());
+    if transcripts.len() < total {
+        eprintln!(
+            "Warning: skipped {} transcript(s) on contigs missing from the reference or past their end",
+            total - transcripts.len()
+        );
+    }
 
-        // Reject sequences with long runs of 'N' (>2)
-        if has_long_n_run(slice, 2) {
-            continue;
+    let tpm = match &args.tpm {
+        Some(tpm_path) => {
+            let table = read_tpm(tpm_path)?;
+            let tpm: Vec<f64> = transcripts.iter().map(|t| table.get(&t.id).copied().unwrap_or(0.0)).collect();
+            let missing = transcripts.iter().filter(|t| !table.contains_key(&t.id)).count();
+            eprintln!("Expression: TPMs from {} ({} transcript(s) not listed, set to 0)", tpm_path.display(), missing);
+            tpm
         }
+        None => {
+            eprintln!("Expression: random log-normal profile (sigma {})", args.expression_sigma);
+            lognormal_tpm(transcripts.len(), args.expression_sigma, rng)?
+        }
+    };
+    if let Some(out) = &args.expression_out {
+        let mut w = create_output(out)?;
+        writeln!(w, "#transcript\tgene\tlength\ttpm")?;
+        for (t, x) in transcripts.iter().zip(&tpm) {
+            writeln!(w, "{}\t{}\t{}\t{}", t.id, t.gene, t.len(), x)?;
+        }
+        w.close()?;
+    }
+    Ok((transcripts, tpm))
+}
 
-        // Build header: use 1-based inclusive coordinates for human-friendliness
-        let header = format!(
-            "seq{:06} src={} range={}..{} len={}",
-            out_records.len() + 1,
-            c.name,
-            start + 1,
-            end,
-            len
-        );
-        out_records.push((header, slice.to_vec()));
+/// Read and concatenate BED files.
+fn read_beds(paths: &[PathBuf]) -> Result<Vec<BedRecord>> {
+    let mut out = Vec::new();
+    for p in paths {
+        out.extend(read_bed(p)?);
     }
+    Ok(out)
+}
 
-    // convert to records for writing
-    let records: Vec<_> = out_records
-        .iter()
-        .map(|(h, s)| FastaRecord { header: h.clone(), seq: s.as_slice() })
-        .collect();
+/// Report the space left by `--include-bed`/`--exclude-bed`, and warn about BED contigs missing from the reference.
+/// Returns the number of allowed bases.
+fn print_mask_summary(reference: &Reference, params: &SeqSampleParams) -> usize {
+    let names: Vec<&str> = (0..reference.len()).map(|i| reference.name(i)).collect();
+    let lengths: Vec<usize> = (0..reference.len()).map(|i| reference.seq_len(i)).collect();
+    let bed = params.include.iter().flatten().chain(&params.exclude);
+    let mut unknown: Vec<&str> = bed.map(|r| r.chrom.as_str()).filter(|c| !names.contains(c)).collect();
+    unknown.sort_unstable();
+    unknown.dedup();
+    if !unknown.is_empty() {
+        eprintln!("Warning: ignoring BED intervals on {} contig(s) not in the reference: {}", unknown.len(), unknown.join(", "));
+    }
+    let circular = params.circular_flags(&names);
+    let segments = allowed_segments(&names, &lengths, &circular, params.include.as_deref(), &params.exclude);
+    let allowed: usize = segments.iter().map(Segment::len).sum();
+    eprintln!(
+        "Allowed regions: {} segment(s), {} of {} bp",
+        segments.len(),
+        allowed,
+        lengths.iter().sum::<usize>()
+    );
+    allowed
+}
+
+/// Bases to sample for `--coverage` of the `allowed` bases, or for `--bases` in total, with a description of the
+/// target.
+fn target_bases(coverage: Option<f64>, bases: Option<f64>, allowed: usize) -> Result<(f64, String)> {
+    match (coverage, bases) {
+        (Some(c), _) => Ok((c * allowed as f64, format!("{}x coverage of {} bp", c, allowed))),
+        (None, Some(b)) => Ok((b, format!("{} bases", b))),
+        (None, None) => Err(anyhow!("one of --n, --coverage or --bases is required")),
+    }
+}
+
+/// Echo the sampling parameters, with lengths drawn from `[min, max]`, and the observed lengths to stderr.
+fn print_summary(params: &SeqSampleParams, max: usize, fragments: &[Fragment]) {
+    let lens = fragments.iter().map(|f| f.seq.len());
+    let (lo, hi) = (lens.clone().min().unwrap_or(0), lens.clone().max().unwrap_or(0));
+    let mean = lens.sum::<usize>() as f64 / fragments.len().max(1) as f64;
+    let minus = fragments.iter().filter(|f| f.reverse).count();
+    eprintln!("Length distribution: {} truncated to [{}, {}]", params.length, params.min, max);
+    eprintln!("Sampled lengths: mean {:.1}, min {}, max {}", mean, lo, hi);
+    eprintln!(
+        "Strand: forward fraction {} ({} of {} fragments on the minus strand)",
+        params.forward_fraction,
+        minus,
+        fragments.len()
+    );
+}
 
-    write_fasta(&records, &args.output, 80)?;
-    eprintln!("Wrote {} sequences to {}", records.len(), args.output.display());
+/// Write the `--truth-bed` and `--truth-tsv` files, one line per fragment named as in the output headers.
+fn write_truth(args: &SeqSampleArgs, fragments: &[Fragment]) -> Result<()> {
+    if let Some(path) = &args.truth_bed {
+        let mut w = create_output(path)?;
+        for (i, f) in fragments.iter().enumerate() {
+            if let Origin::Transcript { blocks, .. } = &f.origin {
+                // A spliced fragment takes one BED line per exon block
+                for b in blocks {
+                    writeln!(w, "{}\t{}\t{}\t{}\t0\t{}", f.contig, b.ref_pos, b.ref_pos + b.ref_len, fragment_name(i + 1), f.strand())?;
+                }
+                continue;
+            }
+            // A fragment spanning the origin of a circular contig is split into two BED lines
+            let (end, wrapped) = match f.circular_len {
+                Some(len) if f.end > len => (len, Some(f.end - len)),
+                _ => (f.end, None),
+            };
+            writeln!(w, "{}\t{}\t{}\t{}\t0\t{}", f.contig, f.start, end, fragment_name(i + 1), f.strand())?;
+            if let Some(wrapped) = wrapped {
+                writeln!(w, "{}\t0\t{}\t{}\t0\t{}", f.contig, wrapped, fragment_name(i + 1), f.strand())?;
+            }
+        }
+        w.close()?;
+    }
+    if let Some(path) = &args.truth_tsv {
+        let mut w = create_output(path)?;
+        writeln!(w, "#name\tcontig\tstart\tend\tlength\tstrand")?;
+        for (i, f) in fragments.iter().enumerate() {
+            let len = f.seq.len();
+            writeln!(w, "{}\t{}\t{}\t{}\t{}\t{}", fragment_name(i + 1), f.contig, f.start + 1, f.end, len, f.strand())?;
+        }
+        w.close()?;
+    }
     Ok(())
 }
 
+/// Write the `--families-out` table, one line per read named as in the output.
+fn write_families(args: &SeqSampleArgs, reads: &[Fragment], families: &[FamilyRead]) -> Result<()> {
+    let Some(path) = &args.families_out else { return Ok(()) };
+    let mut w = create_output(path)?;
+    writeln!(w, "#read\tmolecule\tfamily_size\tumi\tobserved_umi")?;
+    for (i, (f, fam)) in reads.iter().zip(families).enumerate() {
+        let observed = f.umi.as_deref().unwrap_or_default();
+        writeln!(w, "{}\tmol{:06}\t{}\t{}\t{}", fragment_name(i + 1), fam.molecule + 1, fam.family_size, fam.umi, observed)?;
+    }
+    w.close()
+}
+
+/// Report the ambiguous runs fragments avoided and the candidates rejected, per contig, and lengths drawn again.
+fn print_filter_summary(reference: &Reference, params: &SeqSampleParams, stats: &SampleStats) {
+    let per_contig = |counts: &[usize]| -> Vec<String> {
+        counts.iter().enumerate().filter(|(_, &k)| k > 0).map(|(i, k)| format!("{} {}", reference.name(i), k)).collect()
+    };
+    let excluded: usize = stats.excluded.iter().sum();
+    if excluded > 0 {
+        eprintln!(
+            "Ambiguity filter: fragments avoid {} bp in runs of more than {} N or of disallowed symbols: {}",
+            excluded,
+            params.max_n_run,
+            per_contig(&stats.excluded).join(", ")
+        );
+    }
+    let rejected: usize = stats.rejected.iter().sum();
+    if rejected > 0 {
+        eprintln!(
+            "Ambiguity filter rejected {} candidate(s) over --max-ambig-frac: {}",
+            rejected,
+            per_contig(&stats.rejected).join(", ")
+        );
+    }
+    if let Some(longest) = stats.longest {
+        eprintln!(
+            "No allowed region free of ambiguous runs holds more than {} bp: fragment lengths drawn from [{}, {}]",
+            longest, params.min, longest
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::faidx::{extract_region, Region};
+    use crate::parse_args;
     use std::fs::{self, File};
     use std::io::Write;
     use tempfile::tempdir;
 
+    /// `seq_sample` arguments for `reference` and `output` as the CLI parses them: every option at its default, one
+    /// fragment of 1 bp and a fixed seed. Tests override fields with struct update syntax.
+    fn test_args(reference: &Path, output: &Path) -> SeqSampleArgs {
+        let (reference, output) = (reference.to_str().unwrap(), output.to_str().unwrap());
+        parse_args(&["--reference", reference, "--output", output, "--n", "1", "--min", "1", "--max", "1", "--seed", "123"])
+    }
+
     #[test]
     fn rejects_long_n_runs() {
         let dir = tempdir().unwrap();
@@ -185,14 +1418,7 @@ mod tests {
         writeln!(f, ">chrB").unwrap();
         writeln!(f, "ACGTACGTACGTACGT").unwrap();
 
-        let args = SeqSampleArgs {
-            reference: ref_path.clone(),
-            n: 3,
-            min: 4,
-            max: 6,
-            output: out_path.clone(),
-            seed: Some(123),
-        };
+        let args = SeqSampleArgs { n: Some(3), min: 4, max: 6, ..test_args(&ref_path, &out_path) };
         run(args).unwrap();
 
         let out = fs::read_to_string(out_path).unwrap();
@@ -203,4 +1429,286 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn error_table_names_match_paired_reads() {
+        let dir = tempdir().unwrap();
+        let ref_path = dir.path().join("ref.fa");
+        let seq: String = (0..500).map(|i| ['A', 'C', 'G', 'T'][(i * 7 + i / 3) % 4]).collect();
+        fs::write(&ref_path, format!(">chrA\n{}\n", seq)).unwrap();
+        for naming in [PairNaming::Slash, PairNaming::Casava] {
+            let (r1, r2, errors) = (dir.path().join("r1.fq"), dir.path().join("r2.fq"), dir.path().join("errors.tsv"));
+            let args = SeqSampleArgs {
+                n: Some(20),
+                min: 80,
+                max: 120,
+                error_model: Some(ErrorProfile::Illumina),
+                error_rate: Some(0.05),
+                errors_out: Some(errors.clone()),
+                paired: true,
+                output2: Some(r2.clone()),
+                read_length: Some(50),
+                read_names: naming,
+                ..test_args(&ref_path, &r1)
+            };
+            run(args).unwrap();
+            let names: HashSet<String> = [&r1, &r2]
+                .iter()
+                .flat_map(|p| fs::read_to_string(p).unwrap().lines().step_by(4).map(|h| h[1..].to_string()).collect::<Vec<_>>())
+                .collect();
+            let table = fs::read_to_string(&errors).unwrap();
+            let ids: HashSet<&str> = table.lines().skip(1).map(|l| l.split('\t').next().unwrap()).collect();
+            assert!(ids.len() > 10, "{:?}: {} reads with errors", naming, ids.len());
+            for id in ids {
+                assert!(names.iter().any(|h| h.starts_with(&format!("{} ", id))), "{:?}: no read named '{}'", naming, id);
+            }
+        }
+    }
+
+    #[test]
+    fn minus_strand_fragments_are_reverse_complemented() {
+        let contigs = vec![Contig { name: "c".into(), header: "c".into(), seq: b"AACGTRYKMG".to_vec(), qual: None }];
+        let params = SeqSampleParams { n: 5, min: 10, max: 10, forward_fraction: 0.0, ..Default::default() };
+        let mut rng = StdRng::seed_from_u64(1);
+        for f in sample_fragments(&contigs, &params, &mut rng).unwrap() {
+            assert!(f.reverse);
+            assert_eq!(f.seq, b"CKMRYACGTT");
+            assert!(f.header(1).ends_with("range=1..10 len=10 strand=-"));
+        }
+    }
+
+    #[test]
+    fn truth_files_match_headers() {
+        let dir = tempdir().unwrap();
+        let ref_path = dir.path().join("ref.fa");
+        let out_path = dir.path().join("out.fa");
+        fs::write(&ref_path, ">chrA\nACGTACGTAACCGGTTACGT\n>chrB\nTTTTGGGGCCCCAAAA\n").unwrap();
+        let args = SeqSampleArgs {
+            n: Some(5),
+            min: 5,
+            max: 10,
+            truth_bed: Some(dir.path().join("truth.bed")),
+            truth_tsv: Some(dir.path().join("truth.tsv")),
+            ..test_args(&ref_path, &out_path)
+        };
+        run(args).unwrap();
+
+        let out = fs::read_to_string(out_path).unwrap();
+        let headers: Vec<&str> = out.lines().filter_map(|l| l.strip_prefix('>')).collect();
+        let bed = fs::read_to_string(dir.path().join("truth.bed")).unwrap();
+        let tsv = fs::read_to_string(dir.path().join("truth.tsv")).unwrap();
+        assert_eq!(bed.lines().count(), 5);
+        assert_eq!(tsv.lines().count(), 6);
+        for ((h, b), t) in headers.iter().zip(bed.lines()).zip(tsv.lines().skip(1)) {
+            let b: Vec<&str> = b.split('\t').collect();
+            let t: Vec<&str> = t.split('\t').collect();
+            let start: usize = b[1].parse().unwrap();
+            let expected = format!("{} src={} range={}..{} len={} strand={}", b[3], b[0], start + 1, b[2], t[4], b[5]);
+            assert_eq!(*h, expected);
+            assert_eq!(t, [b[3], b[0], &(start + 1).to_string(), b[2], t[4], b[5]]);
+        }
+    }
+
+    #[test]
+    fn fragments_stay_inside_allowed_regions() {
+        let contigs: Vec<Contig> = ["a", "b"]
+            .iter()
+            .map(|n| Contig { name: n.to_string(), header: n.to_string(), seq: vec![b'A'; 1000], qual: None })
+            .collect();
+        let bed = |chrom: &str, start: u64, end: u64| BedRecord { chrom: chrom.into(), start, end, name: None, strand: None };
+        let params = SeqSampleParams {
+            n: 200,
+            min: 20,
+            max: 50,
+            include: Some(vec![bed("a", 100, 300), bed("b", 0, 40), bed("z", 0, 100)]),
+            exclude: vec![bed("a", 150, 160)],
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(7);
+        for f in sample_fragments(&contigs, &params, &mut rng).unwrap() {
+            let inside = match f.contig.as_str() {
+                "a" => (100..=150).contains(&f.start) && f.end <= 150 || f.start >= 160 && f.end <= 300,
+                _ => f.end <= 40,
+            };
+            assert!(inside, "{}", f.describe());
+        }
+        let params = SeqSampleParams { include: Some(vec![bed("b", 0, 10)]), ..params };
+        assert!(sample_fragments(&contigs, &params, &mut rng).is_err());
+    }
+
+    #[test]
+    fn ambiguity_filter_is_configurable() {
+        let params = SeqSampleParams { max_ambig_frac: 0.2, allowed_symbols: Some(b"ACGTN".to_vec()), ..Default::default() };
+        assert!(params.avoids_runs(b"ACGTNACGTN") && params.within_ambig_frac(b"ACGTNACGTN"));
+        assert!(!params.within_ambig_frac(b"ACGTNNCGTN"));
+        assert!(!params.avoids_runs(b"ACGTRACGTA"));
+        assert!(!params.avoids_runs(b"ACNNNACGTA"));
+        assert!(SeqSampleParams { max_n_run: 3, ..Default::default() }.avoids_runs(b"ACNNNACGTA"));
+
+        let contigs = vec![Contig { name: "n".into(), header: "n".into(), seq: b"ACGTNNNNNNNNNNNNNNNNNN".to_vec(), qual: None }];
+        let params = SeqSampleParams { n: 1, min: 10, max: 10, ..Default::default() };
+        let err = sample_fragments(&contigs, &params, &mut StdRng::seed_from_u64(3)).unwrap_err();
+        assert!(err.to_string().contains("passes the ambiguity filter"), "{}", err);
+        // Only the 4 bases before the run and its first 2 N fit, so lengths are truncated to [4, 6]
+        let params = SeqSampleParams { n: 20, min: 4, max: 10, length: LengthDist::parse("normal:10,1").unwrap(), ..params };
+        let fetch = |i: usize, s: usize, e: usize| Ok(contigs[i].seq[s..e].to_vec());
+        let (frags, stats) = sample_by_length(&["n"], &[22], &params, &mut StdRng::seed_from_u64(3), fetch).unwrap();
+        assert!(frags.iter().all(|f| f.start == 0 && f.end <= 6));
+        assert_eq!(stats.longest, Some(6));
+        let params = SeqSampleParams { length: LengthDist::parse("normal:10,0.05").unwrap(), ..params };
+        let err = sample_fragments(&contigs, &params, &mut StdRng::seed_from_u64(3)).unwrap_err();
+        assert!(err.to_string().contains("free of ambiguous runs is longer than 6 bp"), "{}", err);
+    }
+
+    #[test]
+    fn segments_are_split_around_ambiguous_runs() {
+        // A run of 5 N keeps its outer 2 bases usable; windows of 12 fit only on either side of it
+        let seq = b"AAAAAAAAAANNNNNCCCCCCCCCC";
+        let params = SeqSampleParams { n: 300, min: 12, max: 12, ..Default::default() };
+        let fetch = |_: usize, s: usize, e: usize| Ok(seq[s..e].to_vec());
+        let (frags, stats) = sample_by_length(&["a"], &[seq.len()], &params, &mut StdRng::seed_from_u64(5), fetch).unwrap();
+        let starts: HashSet<usize> = frags.iter().map(|f| f.start).collect();
+        assert_eq!(starts, HashSet::from([0, 13]));
+        assert_eq!((stats.excluded, stats.rejected), (vec![5], vec![0]));
+
+        // A run of 3 N is split at one point, so no start is counted by both pieces
+        let seq = b"AAAAAAAAAANNNCCCCCCCCCC";
+        let segment = vec![Segment { contig: 0, start: 0, end: seq.len(), circular: false }];
+        let mut fetch = |_: usize, s: usize, e: usize| Ok(seq[s..e].to_vec());
+        let (pieces, _) = avoid_ambiguous_runs(segment, &[seq.len()], &params, &mut fetch).unwrap();
+        assert_eq!(pieces.iter().map(|p| (p.start, p.end)).collect::<Vec<_>>(), [(0, 12), (12, 23)]);
+
+        // On a circle, N at both ends form one run of 3 across the origin
+        let seq = b"NAAAAAAAAAANN";
+        let params = SeqSampleParams { circular: vec!["c".into()], ..params };
+        let fetch = |_: usize, s: usize, e: usize| Ok(seq[s..e].to_vec());
+        let (frags, stats) = sample_by_length(&["c"], &[seq.len()], &params, &mut StdRng::seed_from_u64(5), fetch).unwrap();
+        let starts: HashSet<usize> = frags.iter().map(|f| f.start).collect();
+        // Start 12 would leave the run past its cut, at 13
+        assert_eq!(starts, HashSet::from([0, 1]));
+        assert_eq!(stats.excluded, vec![3]);
+
+        let params = SeqSampleParams { allowed_symbols: Some(b"ACGT".to_vec()), circular: Vec::new(), ..params };
+        let seq = b"AAAAAAAAAAAARCCCCCCCCCCCC";
+        let fetch = |_: usize, s: usize, e: usize| Ok(seq[s..e].to_vec());
+        let (frags, _) = sample_by_length(&["a"], &[seq.len()], &params, &mut StdRng::seed_from_u64(5), fetch).unwrap();
+        assert!(frags.iter().all(|f| !f.seq.contains(&b'R') && !f.seq.contains(&b'Y')));
+    }
+
+    #[test]
+    fn coverage_and_bases_set_the_fragment_count() {
+        assert_eq!(parse_coverage("30x"), Ok(30.0));
+        assert_eq!(parse_bases("1G"), Ok(1e9));
+        assert_eq!(parse_bases("2.5kbp"), Ok(2500.0));
+        assert!(parse_bases("12q").is_err() && parse_coverage("-3x").is_err());
+
+        assert_eq!(target_bases(Some(30.0), None, 10_000).unwrap().0, 300_000.0);
+        let params = SeqSampleParams { bases: Some(1501.0), min: 100, max: 200, ..Default::default() };
+        assert_eq!(params.count(200).unwrap(), 11);
+        assert_eq!(params.count(100).unwrap(), 16);
+    }
+
+    #[test]
+    fn coverage_counts_lengths_clipped_to_the_reference() {
+        let dir = tempdir().unwrap();
+        let ref_path = dir.path().join("ref.fa");
+        let out_path = dir.path().join("out.fa");
+        let seq: String = (0..1000).map(|i| ['A', 'C', 'G', 'T'][(i * 7 + i / 3) % 4]).collect();
+        fs::write(&ref_path, format!(">c\n{}\n", seq)).unwrap();
+
+        // --max is longer than the reference, so lengths are drawn from [50, 1000] and the count follows
+        let args = SeqSampleArgs { n: None, coverage: Some(30.0), min: 50, max: 5000, ..test_args(&ref_path, &out_path) };
+        run(args).unwrap();
+        let out = fs::read_to_string(out_path).unwrap();
+        assert_eq!(out.lines().filter(|l| l.starts_with('>')).count(), 58);
+        let bases: usize = out.lines().filter(|l| !l.starts_with('>')).map(str::len).sum();
+        assert!((24_000..36_000).contains(&bases), "{} bases for 30x of 1 kb", bases);
+    }
+
+    #[test]
+    fn start_table_enumerates_every_valid_start_once() {
+        let seg = |contig, start, end| Segment { contig, start, end, circular: false };
+        let segments = vec![seg(0, 0, 5), seg(0, 10, 30), seg(1, 3, 4), seg(2, 0, 12)];
+        let table = StartTable::new(segments.clone());
+        for len in 1..=21 {
+            let mut expected: Vec<(usize, usize)> =
+                segments.iter().filter(|s| s.len() >= len).flat_map(|s| (s.start..=s.end - len).map(move |p| (s.contig, p))).collect();
+            let mut got: Vec<(usize, usize)> = (0..table.total(len)).map(|u| table.nth(len, u)).collect();
+            expected.sort_unstable();
+            got.sort_unstable();
+            assert_eq!(got, expected, "len {}", len);
+        }
+        assert!(table.pick(21, &mut StdRng::seed_from_u64(1)).is_none());
+    }
+
+    #[test]
+    fn circular_contigs_wrap_around_the_origin() {
+        let (names, lengths) = (["ring", "line"], [10, 30]);
+        let bed = |chrom: &str, start: u64, end: u64| BedRecord { chrom: chrom.into(), start, end, name: None, strand: None };
+        let whole = allowed_segments(&names, &lengths, &[true, false], None, &[]);
+        assert_eq!(whole[0], Segment { contig: 0, start: 0, end: 10, circular: true });
+        let masked = allowed_segments(&names, &lengths, &[true, false], None, &[bed("ring", 4, 6)]);
+        assert_eq!(masked[0], Segment { contig: 0, start: 6, end: 14, circular: false });
+
+        // Every one of the 10 positions of the ring is a valid start for any length up to 10
+        let table = StartTable::new(whole);
+        assert_eq!(table.total(8), 10 + 23);
+        assert_eq!(table.total(11), 20);
+
+        let contigs = vec![Contig { name: "ring".into(), header: "ring topology=circular".into(), seq: b"AACCGGTTAC".to_vec(), qual: None }];
+        let params = SeqSampleParams {
+            n: 50,
+            min: 6,
+            max: 6,
+            forward_fraction: 1.0,
+            circular: vec!["ring".into()],
+            ..Default::default()
+        };
+        let frags = sample_fragments(&contigs, &params, &mut StdRng::seed_from_u64(4)).unwrap();
+        let doubled = b"AACCGGTTACAACCGGTTAC";
+        assert!(frags.iter().all(|f| f.seq == doubled[f.start..f.end]));
+        let wrapped = frags.iter().find(|f| f.end > 10).expect("no fragment spans the origin");
+        assert!(wrapped.describe().ends_with("topology=circular"));
+        let region = Region::from_header(&wrapped.header(1)).unwrap().unwrap();
+        let mut reference = Reference::Loaded(contigs.clone());
+        assert_eq!(extract_region(&mut reference, &region).unwrap().1, wrapped.seq);
+    }
+
+    #[test]
+    fn families_table_matches_read_headers() {
+        let dir = tempdir().unwrap();
+        let ref_path = dir.path().join("ref.fa");
+        let out_path = dir.path().join("out.fa");
+        let families = dir.path().join("families.tsv");
+        let seq: String = (0..2000).map(|i| ['A', 'C', 'G', 'T'][(i * 7 + i / 3) % 4]).collect();
+        fs::write(&ref_path, format!(">chrA\n{}\n", seq)).unwrap();
+        let args = SeqSampleArgs {
+            n: Some(50),
+            min: 50,
+            max: 100,
+            duplicates: Some("geometric:4".into()),
+            umi_length: 10,
+            families_out: Some(families.clone()),
+            ..test_args(&ref_path, &out_path)
+        };
+        run(args).unwrap();
+
+        let out = fs::read_to_string(out_path).unwrap();
+        let headers: Vec<&str> = out.lines().filter_map(|l| l.strip_prefix('>')).collect();
+        let table = fs::read_to_string(families).unwrap();
+        let rows: Vec<Vec<&str>> = table.lines().skip(1).map(|l| l.split('\t').collect()).collect();
+        assert_eq!(rows.len(), headers.len());
+        assert!(headers.len() > 100);
+        let mut by_molecule: HashMap<&str, Vec<(&str, &str)>> = HashMap::new();
+        for (h, row) in headers.iter().zip(&rows) {
+            assert!(h.starts_with(&format!("{} ", row[0])));
+            // Without UMI errors every read carries its molecule's UMI
+            assert_eq!(row[3], row[4]);
+            assert!(h.ends_with(&format!(" umi={}", row[4])));
+            let range = h.split_whitespace().find(|t| t.starts_with("range=")).unwrap();
+            by_molecule.entry(row[1]).or_default().push((range, row[3]));
+        }
+        assert_eq!(by_molecule.len(), 50);
+        assert!(by_molecule.values().all(|reads| reads.iter().all(|r| r == &reads[0])));
+    }
 }
@@ -1,25 +1,39 @@
 //! Core I/O utilities used across `limpet`.
 //!
-//! Supports **FASTA** and **FASTQ** inputs (both plain text and `.gz`) and provides FASTA writing.
-//! The reader returns [`Contig`] records with both the *accession* (`name`) and the **full header**
+//! Supports **FASTA** and **FASTQ** inputs (plain, gzip/BGZF, zstd, bzip2 or xz) and provides FASTA and FASTQ writing.
+//! Every input or output path may be `-`, meaning stdin or stdout.
+//! Records are read one at a time through [`RecordReader`], so commands can stream files of any size
+//! with flat memory. Each [`Contig`] carries both the *accession* (`name`) and the **full header**
 //! string (`header`), enabling provenance‑preserving workflows (e.g., `scramble`).
 //!
 //! ### Design notes
 //! - Input **format is auto‑detected** from the first non‑empty line (`'>'` → FASTA, `'@'` → FASTQ).
-//! - Sequences are upper‑cased ASCII (`A/C/G/T/N` etc.) and stored in memory.
-//! - FASTQ qualities are consumed (for `sample`) but not retained in [`Contig`], since tools that need
-//!   exact raw records (like `sample`) work in a streaming mode rather than via [`Contig`].
+//! - Input **compression is auto‑detected** from the stream's magic bytes ([`Codec::from_magic`]), not the file
+//!   name, so pipes and misnamed files work too.
+//! - Output compression follows the file extension ([`Codec::from_extension`]) unless a codec is given explicitly
+//!   (the commands' `--compress` option); stdout is plain by default.
+//! - Sequences are upper‑cased ASCII (`A/C/G/T/N` etc.) with non‑letters dropped, unless the reader is switched to
+//!   [`SeqMode::Preserve`], which keeps every non‑whitespace byte as written (soft‑masking, `-` gaps, `*` stops).
+//! - FASTQ qualities are kept in [`Contig::qual`] (one byte per retained base), so commands can write FASTQ
+//!   back out with [`FastqWriter`]. Tools that need exact raw records (like `sample`) use
+//!   [`RecordReader::next_raw`], which shares the parser's record boundaries.
+//! - [`read_sequences`] is a convenience wrapper that collects a whole file into memory.
 
+use crate::bgzf::BgzfWriter;
 use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
 use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
 use std::path::Path;
 
 /// A single contig / record
 #[derive(Debug, Clone)]
 /// A parsed record with a canonical accession (`name`), the full original header (`header`)
-/// without the leading `'>'`/`'@'`, and the uppercase biological sequence (`seq`).
+/// without the leading `'>'`/`'@'`, the uppercase biological sequence (`seq`) and, for FASTQ input,
+/// the quality string (`qual`).
 pub struct Contig {
     /// First token of the header (accession)
     pub name: String,
@@ -27,155 +41,759 @@ pub struct Contig {
     pub header: String,
     /// Uppercase sequence
     pub seq: Vec<u8>,
+    /// Phred+33 qualities, same length as `seq` (FASTQ only)
+    pub qual: Option<Vec<u8>>,
 }
 
-enum Format {
+/// Sequence file format, detected from the first non-empty line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
     Fasta,
     Fastq,
 }
 
-fn is_gz(path: &Path) -> bool {
-    path.extension().map(|e| e.eq_ignore_ascii_case("gz")).unwrap_or(false)
+/// How sequence bytes are normalised while reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeqMode {
+    /// Upper-case letters and drop everything else (the historical behaviour)
+    #[default]
+    Normalize,
+    /// Keep every non-whitespace byte exactly as written
+    Preserve,
 }
 
-fn open_maybe_gz(path: &Path) -> Result<Box<dyn BufRead>> {
-    let f = File::open(path)
-        .with_context(|| format!("Failed to open input: {}", path.display()))?;
-    if is_gz(path) {
-        let gz = MultiGzDecoder::new(f);
-        Ok(Box::new(BufReader::new(gz)))
+/// Path that stands for stdin (inputs) or stdout (outputs).
+pub const STDIO_PATH: &str = "-";
+
+/// True if `path` is [`STDIO_PATH`].
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO_PATH
+}
+
+/// Compression codec for inputs and outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Codec {
+    /// Uncompressed
+    None,
+    /// gzip (multi-member streams, including BGZF, are read transparently)
+    Gzip,
+    /// Blocked gzip as produced by `bgzip`
+    Bgzf,
+    /// Zstandard
+    Zstd,
+    /// bzip2
+    Bzip2,
+    /// xz / LZMA2
+    Xz,
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// Bytes needed to recognise every supported magic number (BGZF needs its `BC` extra subfield).
+const MAGIC_LEN: usize = 16;
+
+impl Codec {
+    /// Detect the codec from the leading bytes of a stream.
+    pub fn from_magic(magic: &[u8]) -> Codec {
+        if magic.starts_with(GZIP_MAGIC) {
+            // BGZF: FEXTRA flag set and a 'BC' subfield first in the extra field
+            let bgzf = magic.len() >= 14 && magic[3] & 0x04 != 0 && &magic[12..14] == b"BC";
+            if bgzf { Codec::Bgzf } else { Codec::Gzip }
+        } else if magic.starts_with(ZSTD_MAGIC) {
+            Codec::Zstd
+        } else if magic.starts_with(BZIP2_MAGIC) {
+            Codec::Bzip2
+        } else if magic.starts_with(XZ_MAGIC) {
+            Codec::Xz
+        } else {
+            Codec::None
+        }
+    }
+
+    /// Pick an output codec from a file extension (`.gz`, `.bgz`, `.zst`, `.bz2`, `.xz`).
+    pub fn from_extension(path: &Path) -> Codec {
+        let ext = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+        match ext.as_str() {
+            "gz" | "gzip" => Codec::Gzip,
+            "bgz" | "bgzf" => Codec::Bgzf,
+            "zst" | "zstd" => Codec::Zstd,
+            "bz2" => Codec::Bzip2,
+            "xz" => Codec::Xz,
+            _ => Codec::None,
+        }
+    }
+
+    /// True for any codec other than [`Codec::None`].
+    pub fn is_compressed(self) -> bool {
+        self != Codec::None
+    }
+}
+
+/// Reader that replays peeked bytes before continuing with the rest of the stream.
+type Replay<R> = io::Chain<io::Cursor<Vec<u8>>, R>;
+
+/// Read up to `n` leading bytes from `r` and return them with a reader that replays them first.
+fn peek_bytes<R: Read>(mut r: R, n: usize) -> Result<(Vec<u8>, Replay<R>)> {
+    let mut buf = vec![0u8; n];
+    let mut got = 0usize;
+    while got < n {
+        match r.read(&mut buf[got..]) {
+            Ok(0) => break,
+            Ok(k) => got += k,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    buf.truncate(got);
+    Ok((buf.clone(), io::Cursor::new(buf).chain(r)))
+}
+
+fn open_raw(path: &Path) -> Result<Box<dyn Read>> {
+    if is_stdio(path) {
+        Ok(Box::new(io::stdin()))
     } else {
-        Ok(Box::new(BufReader::new(f)))
-    }
-}
-
-fn detect_format(path: &Path) -> Result<Format> {
-    let mut rdr = open_maybe_gz(path)?;
-    let mut line = String::new();
-    loop {
-        line.clear();
-        let bytes = rdr.read_line(&mut line)?;
-        if bytes == 0 { break; }
-        let s = line.trim_end();
-        if s.is_empty() { continue; }
-        let first = s.as_bytes()[0];
-        return match first {
-            b'>' => Ok(Format::Fasta),
-            b'@' => Ok(Format::Fastq),
-            _ => Err(anyhow!("Unrecognized input format: first non-empty line did not start with '>' (FASTA) or '@' (FASTQ)")),
+        Ok(Box::new(File::open(path).with_context(|| format!("Failed to open input: {}", path.display()))?))
+    }
+}
+
+/// Detect the compression codec of a file from its magic bytes.
+pub fn sniff_codec<P: AsRef<Path>>(path: P) -> Result<Codec> {
+    let (magic, _) = peek_bytes(open_raw(path.as_ref())?, MAGIC_LEN)?;
+    Ok(Codec::from_magic(&magic))
+}
+
+/// Open an input path (or `-` for stdin), transparently decompressing gzip/BGZF, zstd, bzip2 or xz
+/// as detected from the stream's magic bytes.
+pub fn open_input<P: AsRef<Path>>(path: P) -> Result<Box<dyn BufRead>> {
+    let path = path.as_ref();
+    let (magic, rdr) = peek_bytes(open_raw(path)?, MAGIC_LEN)?;
+    let decoded: Box<dyn Read> = match Codec::from_magic(&magic) {
+        Codec::None => return Ok(Box::new(BufReader::new(rdr))),
+        Codec::Gzip | Codec::Bgzf => Box::new(MultiGzDecoder::new(rdr)),
+        Codec::Zstd => Box::new(
+            zstd::stream::read::Decoder::new(rdr)
+                .with_context(|| format!("Failed to start zstd decoder for {}", path.display()))?,
+        ),
+        Codec::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(rdr)),
+        Codec::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(rdr)),
+    };
+    Ok(Box::new(BufReader::new(decoded)))
+}
+
+/// A writer that can be finalised (compressors write trailers on finish).
+trait FinishWrite: Write {
+    fn finish_boxed(self: Box<Self>) -> io::Result<()>;
+}
+
+impl<W: Write> FinishWrite for BufWriter<W> {
+    fn finish_boxed(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl<W: Write> FinishWrite for GzEncoder<W> {
+    fn finish_boxed(self: Box<Self>) -> io::Result<()> {
+        self.finish()?.flush()
+    }
+}
+
+impl<W: Write> FinishWrite for BgzfWriter<W> {
+    fn finish_boxed(self: Box<Self>) -> io::Result<()> {
+        self.finish().map(|_| ())
+    }
+}
+
+impl<W: Write> FinishWrite for zstd::stream::write::Encoder<'static, W> {
+    fn finish_boxed(self: Box<Self>) -> io::Result<()> {
+        self.finish()?.flush()
+    }
+}
+
+impl<W: Write> FinishWrite for bzip2::write::BzEncoder<W> {
+    fn finish_boxed(self: Box<Self>) -> io::Result<()> {
+        self.finish()?.flush()
+    }
+}
+
+impl<W: Write> FinishWrite for xz2::write::XzEncoder<W> {
+    fn finish_boxed(self: Box<Self>) -> io::Result<()> {
+        self.finish()?.flush()
+    }
+}
+
+/// Output sink returned by [`create_output`]. Call [`Output::close`] when done so that compressed
+/// streams are finalised and write errors are reported.
+pub struct Output {
+    inner: Box<dyn FinishWrite>,
+}
+
+impl Output {
+    /// Flush and finalise the stream.
+    pub fn close(self) -> Result<()> {
+        self.inner.finish_boxed()?;
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Create an output path (or `-` for stdout), compressed according to its extension (see [`Codec::from_extension`]).
+pub fn create_output<P: AsRef<Path>>(path: P) -> Result<Output> {
+    create_output_with(path, None)
+}
+
+/// Create an output path (or `-` for stdout) with an explicit codec; `None` falls back to the extension.
+/// Stdout is plain unless a codec is given.
+pub fn create_output_with<P: AsRef<Path>>(path: P, codec: Option<Codec>) -> Result<Output> {
+    let path = path.as_ref();
+    let w: BufWriter<Box<dyn Write>> = if is_stdio(path) {
+        BufWriter::new(Box::new(io::stdout()))
+    } else {
+        let fh = File::create(path).with_context(|| format!("Failed to create output: {}", path.display()))?;
+        BufWriter::new(Box::new(fh))
+    };
+    let codec = codec.unwrap_or_else(|| if is_stdio(path) { Codec::None } else { Codec::from_extension(path) });
+    let inner: Box<dyn FinishWrite> = match codec {
+        Codec::None => Box::new(w),
+        Codec::Gzip => Box::new(GzEncoder::new(w, Compression::default())),
+        Codec::Bgzf => Box::new(BgzfWriter::new(w)),
+        Codec::Zstd => Box::new(
+            zstd::stream::write::Encoder::new(w, 0)
+                .with_context(|| format!("Failed to start zstd encoder for {}", path.display()))?,
+        ),
+        Codec::Bzip2 => Box::new(bzip2::write::BzEncoder::new(w, bzip2::Compression::default())),
+        Codec::Xz => Box::new(xz2::write::XzEncoder::new(w, 6)),
+    };
+    Ok(Output { inner })
+}
+
+/// Split a header line (without the leading marker) into `(name, header)`.
+pub(crate) fn split_header(raw: &[u8]) -> (String, String) {
+    let header_full = String::from_utf8_lossy(raw).trim().to_string();
+    let name = header_full.split_whitespace().next().unwrap_or("").to_string();
+    (name, header_full)
+}
+
+/// Map one input byte to its stored sequence byte, or `None` if it is dropped.
+fn keep_base(b: u8, mode: SeqMode) -> Option<u8> {
+    match mode {
+        SeqMode::Normalize if b.is_ascii_alphabetic() => Some(b.to_ascii_uppercase()),
+        SeqMode::Preserve if !b.is_ascii_whitespace() => Some(b),
+        _ => None,
+    }
+}
+
+/// Append the retained bytes of `line` to `seq`.
+fn push_bases(seq: &mut Vec<u8>, line: &[u8], mode: SeqMode) {
+    seq.extend(line.iter().filter_map(|&b| keep_base(b, mode)));
+}
+
+pub(crate) fn trim_end(line: &[u8]) -> &[u8] {
+    let mut end = line.len();
+    while end > 0 && line[end - 1].is_ascii_whitespace() {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// Complement of one nucleotide symbol (IUPAC codes included, `U` → `A`), keeping its case.
+/// Anything else (gaps, `N`, `S`, `W`, protein letters) is returned unchanged.
+pub fn complement(b: u8) -> u8 {
+    let c = match b.to_ascii_uppercase() {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' | b'U' => b'A',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        _ => return b,
+    };
+    if b.is_ascii_lowercase() { c.to_ascii_lowercase() } else { c }
+}
+
+/// Reverse complement of a nucleotide sequence (see [`complement`]).
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter().rev().map(|&b| complement(b)).collect()
+}
+
+/// One record together with the exact bytes it occupied in the input.
+#[derive(Debug, Clone)]
+pub struct RawRecord {
+    /// Parsed view of the record
+    pub parsed: Contig,
+    /// Original text, including line wrapping and line endings (always ends with a newline)
+    pub bytes: Vec<u8>,
+}
+
+/// Alphabet used for strict validation (see [`RecordReader::with_alphabet`]). Matching is case-insensitive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Alphabet {
+    /// A, C, G, T, N
+    Dna,
+    /// A, C, G, U, N
+    Rna,
+    /// IUPAC nucleotide codes (ACGTU RYSWKM BDHV N) plus `-` and `.` gaps
+    Iupac,
+    /// The 20 standard amino acids, B/Z/J/U/O/X ambiguity and rare residues, `*` stop and `-` gap
+    Protein,
+}
+
+impl Alphabet {
+    /// True if `b` (either case) belongs to the alphabet.
+    pub fn allows(self, b: u8) -> bool {
+        let set: &[u8] = match self {
+            Alphabet::Dna => b"ACGTN",
+            Alphabet::Rna => b"ACGUN",
+            Alphabet::Iupac => b"ACGTURYSWKMBDHVN-.",
+            Alphabet::Protein => b"ACDEFGHIKLMNPQRSTVWYBZJUOX*-",
         };
+        set.contains(&b.to_ascii_uppercase())
+    }
+
+    /// Lower-case name, as accepted on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Alphabet::Dna => "dna",
+            Alphabet::Rna => "rna",
+            Alphabet::Iupac => "iupac",
+            Alphabet::Protein => "protein",
+        }
+    }
+}
+
+/// Category of a [`SeqError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeqErrorKind {
+    /// Structural problem (bad header, truncated record); the stream cannot be resumed.
+    Format,
+    /// Sequence symbol outside the requested alphabet; the record was consumed and reading may continue.
+    Symbol,
+    /// FASTQ quality problem (length mismatch, value out of range); reading may continue.
+    Quality,
+}
+
+/// Parse or validation error with its location in the input.
+#[derive(Debug, Clone)]
+pub struct SeqError {
+    pub kind: SeqErrorKind,
+    /// Input name (file path or `stdin`)
+    pub source: String,
+    /// 1-based line number (0 when unknown)
+    pub line: u64,
+    /// 1-based column, where meaningful
+    pub column: Option<usize>,
+    /// Accession of the record being read, if known
+    pub record: Option<String>,
+    pub message: String,
+}
+
+impl SeqError {
+    /// True if the reader can continue with the next record after this error.
+    pub fn is_recoverable(&self) -> bool {
+        self.kind != SeqErrorKind::Format
+    }
+}
+
+impl std::fmt::Display for SeqError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)?;
+        if self.line > 0 {
+            write!(f, ":{}", self.line)?;
+            if let Some(c) = self.column {
+                write!(f, ":{}", c)?;
+            }
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(r) = &self.record {
+            write!(f, " (record '{}')", r)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SeqError {}
+
+/// First offending symbol seen in a record: `(line, column, byte)`.
+type BadSymbol = Option<(u64, usize, u8)>;
+
+/// Streaming FASTA/FASTQ reader yielding one [`Contig`] at a time.
+///
+/// The format is detected from the first non‑empty line when the reader is created. Memory use is
+/// bounded by the largest single record, not by the size of the file. Records can be read parsed
+/// ([`RecordReader::next_record`], or by iterating) or together with their raw bytes
+/// ([`RecordReader::next_raw`]); both paths share the same record boundaries, so blank lines and
+/// wrapped sequence/quality lines are handled identically.
+///
+/// All errors are [`SeqError`]s carrying the input name, line number and record name. With
+/// [`RecordReader::with_alphabet`] the reader also rejects unknown symbols and malformed qualities
+/// instead of silently dropping them.
+pub struct RecordReader<R: BufRead> {
+    rdr: R,
+    format: Format,
+    mode: SeqMode,
+    alphabet: Option<Alphabet>,
+    source: String,
+    line: Vec<u8>,
+    /// Number of lines read so far (1-based number of `line`).
+    line_no: u64,
+    /// Header line (untrimmed) already consumed from the input but not yet turned into a record.
+    pending: Option<Vec<u8>>,
+    /// Line number of `pending`.
+    pending_line: u64,
+}
+
+impl RecordReader<Box<dyn BufRead>> {
+    /// Open a FASTA/FASTQ file (optionally compressed, `-` for stdin) for streaming.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path_ref: &Path = path.as_ref();
+        let rdr = open_input(path_ref)?;
+        let source = if is_stdio(path_ref) { "stdin".to_string() } else { path_ref.display().to_string() };
+        RecordReader::named(rdr, source)
     }
-    Err(anyhow!("Input appears empty: {}", path.display()))
 }
 
-fn parse_fasta<R: BufRead>(reader: R) -> Result<Vec<Contig>> {
-    let mut contigs: Vec<Contig> = Vec::new();
-    let mut current_name: Option<String> = None;
-    let mut current_header: Option<String> = None;
-    let mut current_seq: Vec<u8> = Vec::new();
+impl<R: BufRead> RecordReader<R> {
+    /// Wrap an already opened reader, detecting the format from its first non‑empty line.
+    pub fn new(rdr: R) -> Result<Self> {
+        RecordReader::named(rdr, "<input>")
+    }
 
-    for line_res in reader.lines() {
-        let line = line_res?;
-        if line.is_empty() { continue; }
-        if line.starts_with('>') {
-            // flush previous
-            if let Some(name) = current_name.take() {
-                let header = current_header.take().unwrap_or_else(|| name.clone());
-                contigs.push(Contig { name, header, seq: current_seq.clone() });
-                current_seq.clear();
+    /// Like [`RecordReader::new`], naming the input in error messages.
+    pub fn named<S: Into<String>>(mut rdr: R, source: S) -> Result<Self> {
+        let source = source.into();
+        let mut line = Vec::new();
+        let mut line_no = 0u64;
+        loop {
+            line.clear();
+            if rdr.read_until(b'\n', &mut line)? == 0 {
+                let e = SeqError {
+                    kind: SeqErrorKind::Format,
+                    source,
+                    line: 0,
+                    column: None,
+                    record: None,
+                    message: "Input appears empty".to_string(),
+                };
+                return Err(e.into());
             }
-            // capture full header and name token
-            let header_full = line[1..].trim().to_string();
-            let name = header_full.split_whitespace().next().unwrap_or(header_full.as_str()).to_string();
-            current_name = Some(name);
-            current_header = Some(header_full);
-        } else {
-            for b in line.bytes() {
-                let c = b as char;
-                if c.is_ascii_alphabetic() {
-                    current_seq.push(c.to_ascii_uppercase() as u8);
+            line_no += 1;
+            let s = trim_end(&line);
+            if s.is_empty() { continue; }
+            let format = match s[0] {
+                b'>' => Format::Fasta,
+                b'@' => Format::Fastq,
+                _ => {
+                    let e = SeqError {
+                        kind: SeqErrorKind::Format,
+                        source,
+                        line: line_no,
+                        column: Some(1),
+                        record: None,
+                        message: "Unrecognized input format: first non-empty line did not start with '>' (FASTA) or '@' (FASTQ)".to_string(),
+                    };
+                    return Err(e.into());
                 }
+            };
+            let first = line.clone();
+            return Ok(RecordReader {
+                rdr,
+                format,
+                mode: SeqMode::default(),
+                alphabet: None,
+                source,
+                line,
+                line_no,
+                pending: Some(first),
+                pending_line: line_no,
+            });
+        }
+    }
+
+    /// Choose how sequence bytes are normalised (default [`SeqMode::Normalize`]).
+    pub fn with_mode(mut self, mode: SeqMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Enable strict validation: every sequence symbol must belong to `alphabet`, and FASTQ
+    /// qualities must be printable Phred+33 of exactly the sequence length.
+    pub fn with_alphabet(mut self, alphabet: Alphabet) -> Self {
+        self.alphabet = Some(alphabet);
+        self
+    }
+
+    /// Format detected for this input.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Name of the input used in error messages.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Read the next record, or `None` at end of input.
+    pub fn next_record(&mut self) -> Result<Option<Contig>> {
+        self.next_inner(None)
+    }
+
+    /// Read the next record along with its exact original bytes, or `None` at end of input.
+    pub fn next_raw(&mut self) -> Result<Option<RawRecord>> {
+        let mut bytes = Vec::new();
+        Ok(self.next_inner(Some(&mut bytes))?.map(|parsed| {
+            if !bytes.ends_with(b"\n") {
+                bytes.push(b'\n');
             }
+            RawRecord { parsed, bytes }
+        }))
+    }
+
+    /// Iterate over records with their raw bytes.
+    pub fn raw_records(self) -> RawRecords<R> {
+        RawRecords { inner: self }
+    }
+
+    fn next_inner(&mut self, raw: Option<&mut Vec<u8>>) -> Result<Option<Contig>> {
+        match self.format {
+            Format::Fasta => self.next_fasta(raw),
+            Format::Fastq => self.next_fastq(raw),
         }
     }
-    if let Some(name) = current_name.take() {
-        let header = current_header.take().unwrap_or_else(|| name.clone());
-        contigs.push(Contig { name, header, seq: current_seq });
+
+    /// Read one line into `self.line`; returns `false` at EOF.
+    fn read_line(&mut self) -> Result<bool> {
+        self.line.clear();
+        let more = self.rdr.read_until(b'\n', &mut self.line)? > 0;
+        if more {
+            self.line_no += 1;
+        }
+        Ok(more)
     }
-    if contigs.is_empty() {
-        return Err(anyhow!("No sequences found in FASTA."));
+
+    fn error(&self, kind: SeqErrorKind, line: u64, column: Option<usize>, record: &str, message: String) -> anyhow::Error {
+        let record = if record.is_empty() { None } else { Some(record.to_string()) };
+        SeqError { kind, source: self.source.clone(), line, column, record, message }.into()
     }
-    Ok(contigs)
-}
 
-fn parse_fastq<R: BufRead>(mut reader: R) -> Result<Vec<Contig>> {
-    // Robust FASTQ parser supporting wrapped sequence/quality.
-    let mut contigs: Vec<Contig> = Vec::new();
-    let mut line = String::new();
+    /// Remember the first symbol of the current line outside the strict alphabet, if any.
+    fn check_symbols(&self, line: &[u8], bad: &mut BadSymbol) {
+        if bad.is_some() {
+            return;
+        }
+        if let Some(alphabet) = self.alphabet {
+            if let Some(col) = line.iter().position(|&b| !b.is_ascii_whitespace() && !alphabet.allows(b)) {
+                *bad = Some((self.line_no, col + 1, line[col]));
+            }
+        }
+    }
 
-    loop {
-        line.clear();
-        if reader.read_line(&mut line)? == 0 {
-            break; // EOF
+    fn symbol_error(&self, bad: BadSymbol, record: &str) -> Result<()> {
+        match (bad, self.alphabet) {
+            (Some((line, col, b)), Some(alphabet)) => Err(self.error(
+                SeqErrorKind::Symbol,
+                line,
+                Some(col),
+                record,
+                format!("Invalid symbol '{}' for {} alphabet", b.escape_ascii(), alphabet.name()),
+            )),
+            _ => Ok(()),
         }
-        let header = line.trim_end().to_string();
-        if header.is_empty() { continue; }
-        if !header.starts_with('@') {
-            return Err(anyhow!("FASTQ record does not start with '@' header"));
+    }
+
+    fn next_fasta(&mut self, mut raw: Option<&mut Vec<u8>>) -> Result<Option<Contig>> {
+        let header = match self.pending.take() {
+            Some(h) => h,
+            None => return Ok(None),
+        };
+        if let Some(r) = raw.as_deref_mut() {
+            r.extend_from_slice(&header);
+        }
+        let (name, header_full) = split_header(&trim_end(&header)[1..]);
+        let mut seq: Vec<u8> = Vec::new();
+        let mut bad: BadSymbol = None;
+        while self.read_line()? {
+            let s = trim_end(&self.line);
+            if s.first() == Some(&b'>') {
+                self.pending = Some(self.line.clone());
+                self.pending_line = self.line_no;
+                break;
+            }
+            if let Some(r) = raw.as_deref_mut() {
+                r.extend_from_slice(&self.line);
+            }
+            self.check_symbols(s, &mut bad);
+            push_bases(&mut seq, s, self.mode);
+        }
+        self.symbol_error(bad, &name)?;
+        Ok(Some(Contig { name, header: header_full, seq, qual: None }))
+    }
+
+    fn next_fastq(&mut self, mut raw: Option<&mut Vec<u8>>) -> Result<Option<Contig>> {
+        // Robust FASTQ parser supporting wrapped sequence/quality.
+        let (header, header_line) = match self.pending.take() {
+            Some(h) => (h, self.pending_line),
+            None => loop {
+                if !self.read_line()? {
+                    return Ok(None); // EOF
+                }
+                if trim_end(&self.line).is_empty() { continue; }
+                break (self.line.clone(), self.line_no);
+            },
+        };
+        if header[0] != b'@' {
+            return Err(self.error(
+                SeqErrorKind::Format,
+                header_line,
+                Some(1),
+                "",
+                "FASTQ record does not start with '@' header".to_string(),
+            ));
+        }
+        if let Some(r) = raw.as_deref_mut() {
+            r.extend_from_slice(&header);
         }
-        let header_full = header[1..].trim().to_string();
-        let name = header_full.split_whitespace().next().unwrap_or("").to_string();
+        let (name, header_full) = split_header(&trim_end(&header)[1..]);
 
-        // Read sequence lines until '+' line
-        let mut seq_buf: Vec<u8> = Vec::new();
+        // Read raw sequence lines until '+' line
+        let mut raw_seq: Vec<u8> = Vec::new();
+        let mut bad: BadSymbol = None;
         loop {
-            line.clear();
-            if reader.read_line(&mut line)? == 0 {
-                return Err(anyhow!("Unexpected EOF while reading FASTQ sequence"));
+            if !self.read_line()? {
+                return Err(self.error(
+                    SeqErrorKind::Format,
+                    self.line_no,
+                    None,
+                    &name,
+                    "Unexpected EOF while reading FASTQ sequence".to_string(),
+                ));
+            }
+            if let Some(r) = raw.as_deref_mut() {
+                r.extend_from_slice(&self.line);
+            }
+            let s = trim_end(&self.line);
+            if s.starts_with(b"+") { break; } // next stage
+            self.check_symbols(s, &mut bad);
+            raw_seq.extend_from_slice(s);
+        }
+
+        // Read quality lines until we have as many quality chars as sequence chars
+        let mut raw_qual: Vec<u8> = Vec::with_capacity(raw_seq.len());
+        let mut bad_qual: BadSymbol = None;
+        while raw_qual.len() < raw_seq.len() {
+            if !self.read_line()? {
+                return Err(self.error(
+                    SeqErrorKind::Format,
+                    self.line_no,
+                    None,
+                    &name,
+                    "Unexpected EOF while reading FASTQ quality".to_string(),
+                ));
+            }
+            if let Some(r) = raw.as_deref_mut() {
+                r.extend_from_slice(&self.line);
             }
-            let s = line.trim_end();
-            if s.starts_with('+') { break; } // next stage
-            for b in s.bytes() {
-                let c = b as char;
-                if c.is_ascii_alphabetic() {
-                    seq_buf.push(c.to_ascii_uppercase() as u8);
+            let q = trim_end(&self.line);
+            if bad_qual.is_none() {
+                if let Some(col) = q.iter().position(|&b| !(33..=126).contains(&b)) {
+                    bad_qual = Some((self.line_no, col + 1, q[col]));
                 }
             }
+            raw_qual.extend_from_slice(q);
         }
 
-        // Read quality lines until we have as many quality chars as sequence length
-        let mut qlen: usize = 0;
-        while qlen < seq_buf.len() {
-            line.clear();
-            if reader.read_line(&mut line)? == 0 {
-                return Err(anyhow!("Unexpected EOF while reading FASTQ quality"));
+        self.symbol_error(bad, &name)?;
+        if self.alphabet.is_some() {
+            if let Some((line, col, b)) = bad_qual {
+                return Err(self.error(
+                    SeqErrorKind::Quality,
+                    line,
+                    Some(col),
+                    &name,
+                    format!("Quality value '{}' is outside the Phred+33 range", b.escape_ascii()),
+                ));
+            }
+            if raw_qual.len() != raw_seq.len() {
+                return Err(self.error(
+                    SeqErrorKind::Quality,
+                    self.line_no,
+                    None,
+                    &name,
+                    format!("Quality length {} does not match sequence length {}", raw_qual.len(), raw_seq.len()),
+                ));
+            }
+        }
+
+        // Keep qualities aligned with the bases that survive filtering
+        let mut seq: Vec<u8> = Vec::with_capacity(raw_seq.len());
+        let mut qual: Vec<u8> = Vec::with_capacity(raw_seq.len());
+        for (&b, &q) in raw_seq.iter().zip(&raw_qual) {
+            if let Some(base) = keep_base(b, self.mode) {
+                seq.push(base);
+                qual.push(q);
             }
-            let s = line.trim_end();
-            qlen += s.as_bytes().len();
         }
 
-        contigs.push(Contig { name, header: header_full, seq: seq_buf });
+        Ok(Some(Contig { name, header: header_full, seq, qual: Some(qual) }))
     }
+}
 
-    if contigs.is_empty() {
-        return Err(anyhow!("No sequences found in FASTQ."));
+impl<R: BufRead> Iterator for RecordReader<R> {
+    type Item = Result<Contig>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// Iterator over [`RawRecord`]s, created by [`RecordReader::raw_records`].
+pub struct RawRecords<R: BufRead> {
+    inner: RecordReader<R>,
+}
+
+impl<R: BufRead> Iterator for RawRecords<R> {
+    type Item = Result<RawRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_raw().transpose()
     }
-    Ok(contigs)
 }
 
-/// Read a reference/input file that may be FASTA/FASTQ and optionally gzipped.
 /// Read a file that may be FASTA/FASTQ and optionally gzipped.
 ///
 /// *Format detection* is based on the first non‑empty line.
-/// Returns all records in memory for convenience.
+/// Returns all records in memory for convenience; use [`RecordReader`] to stream large inputs.
 pub fn read_sequences<P: AsRef<Path>>(path: P) -> Result<Vec<Contig>> {
-    let path_ref: &Path = path.as_ref();
-    let fmt = detect_format(path_ref)?;
-    let rdr = open_maybe_gz(path_ref)?;
-    let contigs = match fmt {
-        Format::Fasta => parse_fasta(rdr)?,
-        Format::Fastq => parse_fastq(rdr)?,
-    };
+    read_sequences_with(path, SeqMode::Normalize)
+}
+
+/// Like [`read_sequences`], with an explicit [`SeqMode`].
+pub fn read_sequences_with<P: AsRef<Path>>(path: P, mode: SeqMode) -> Result<Vec<Contig>> {
+    let reader = RecordReader::from_path(path)?.with_mode(mode);
+    let fmt = reader.format();
+    let contigs = reader.collect::<Result<Vec<_>>>()?;
+    if contigs.is_empty() {
+        return Err(match fmt {
+            Format::Fasta => anyhow!("No sequences found in FASTA."),
+            Format::Fastq => anyhow!("No sequences found in FASTQ."),
+        });
+    }
     Ok(contigs)
 }
 
@@ -187,22 +805,265 @@ pub struct FastaRecord<'a> {
     pub seq: &'a [u8],
 }
 
-/// Write records to a FASTA file (wrapped to `line_width` chars).
-pub fn write_fasta<P: AsRef<Path>>(records: &[FastaRecord<'_>], path: P, line_width: usize) -> Result<()> {
-    use std::io::Write;
-    let mut fh = std::fs::File::create(&path)
-        .with_context(|| format!("Failed to create output FASTA: {}", path.as_ref().display()))?;
-    let lw = if line_width == 0 { usize::MAX } else { line_width };
+/// Streaming FASTA writer wrapping sequence lines to a fixed width (`0` = no wrapping).
+pub struct FastaWriter<W: Write> {
+    inner: W,
+    line_width: usize,
+}
 
-    for rec in records {
-        writeln!(fh, ">{}", rec.header)?;
+impl FastaWriter<Output> {
+    /// Create (or truncate) a FASTA file at `path` (see [`create_output`]).
+    pub fn create<P: AsRef<Path>>(path: P, line_width: usize) -> Result<Self> {
+        Ok(FastaWriter::new(create_output(path)?, line_width))
+    }
+}
+
+impl<W: Write> FastaWriter<W> {
+    pub fn new(inner: W, line_width: usize) -> Self {
+        FastaWriter { inner, line_width }
+    }
+
+    /// Write a single record.
+    pub fn write_record(&mut self, header: &str, seq: &[u8]) -> Result<()> {
+        let lw = if self.line_width == 0 { usize::MAX } else { self.line_width };
+        writeln!(self.inner, ">{}", header)?;
         let mut start = 0usize;
-        while start < rec.seq.len() {
-            let end = (start + lw).min(rec.seq.len());
-            fh.write_all(&rec.seq[start..end])?;
-            writeln!(fh)?;
+        while start < seq.len() {
+            let end = start.saturating_add(lw).min(seq.len());
+            self.inner.write_all(&seq[start..end])?;
+            writeln!(self.inner)?;
             start = end;
         }
+        Ok(())
+    }
+
+    /// Flush buffered output and return the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Write records to a FASTA file (wrapped to `line_width` chars; `-` for stdout).
+pub fn write_fasta<P: AsRef<Path>>(records: &[FastaRecord<'_>], path: P, line_width: usize) -> Result<()> {
+    let mut w = FastaWriter::create(path, line_width)?;
+    for rec in records {
+        w.write_record(&rec.header, rec.seq)?;
+    }
+    w.finish()?.close()
+}
+
+/// Minimal view for writing FASTQ output.
+pub struct FastqRecord<'a> {
+    pub header: String,
+    pub seq: &'a [u8],
+    pub qual: &'a [u8],
+}
+
+/// Streaming FASTQ writer (four-line records, no wrapping).
+pub struct FastqWriter<W: Write> {
+    inner: W,
+}
+
+impl FastqWriter<Output> {
+    /// Create (or truncate) a FASTQ file at `path` (see [`create_output`]).
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(FastqWriter::new(create_output(path)?))
+    }
+}
+
+impl<W: Write> FastqWriter<W> {
+    pub fn new(inner: W) -> Self {
+        FastqWriter { inner }
+    }
+
+    /// Write a single record; `qual` must be as long as `seq`.
+    pub fn write_record(&mut self, header: &str, seq: &[u8], qual: &[u8]) -> Result<()> {
+        if seq.len() != qual.len() {
+            return Err(anyhow!(
+                "FASTQ record '{}' has {} bases but {} quality values",
+                header,
+                seq.len(),
+                qual.len()
+            ));
+        }
+        writeln!(self.inner, "@{}", header)?;
+        self.inner.write_all(seq)?;
+        self.inner.write_all(b"\n+\n")?;
+        self.inner.write_all(qual)?;
+        writeln!(self.inner)?;
+        Ok(())
+    }
+
+    /// Flush buffered output and return the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Write records to a FASTQ file (`-` for stdout).
+pub fn write_fastq<P: AsRef<Path>>(records: &[FastqRecord<'_>], path: P) -> Result<()> {
+    let mut w = FastqWriter::create(path)?;
+    for rec in records {
+        w.write_record(&rec.header, rec.seq, rec.qual)?;
+    }
+    w.finish()?.close()
+}
+
+/// Writer that emits either FASTA or FASTQ, so commands can mirror their input format.
+pub enum RecordWriter<W: Write> {
+    Fasta(FastaWriter<W>),
+    Fastq(FastqWriter<W>),
+}
+
+impl RecordWriter<Output> {
+    /// Create an output file in the given format (`line_width` applies to FASTA only; see [`create_output`]).
+    pub fn create<P: AsRef<Path>>(path: P, format: Format, line_width: usize) -> Result<Self> {
+        Ok(RecordWriter::new(create_output(path)?, format, line_width))
+    }
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(inner: W, format: Format, line_width: usize) -> Self {
+        match format {
+            Format::Fasta => RecordWriter::Fasta(FastaWriter::new(inner, line_width)),
+            Format::Fastq => RecordWriter::Fastq(FastqWriter::new(inner)),
+        }
+    }
+
+    /// Write a record; qualities are ignored for FASTA and required for FASTQ.
+    pub fn write_record(&mut self, header: &str, seq: &[u8], qual: Option<&[u8]>) -> Result<()> {
+        match self {
+            RecordWriter::Fasta(w) => w.write_record(header, seq),
+            RecordWriter::Fastq(w) => {
+                let qual = qual.ok_or_else(|| anyhow!("Cannot write FASTQ record '{}' without qualities", header))?;
+                w.write_record(header, seq, qual)
+            }
+        }
+    }
+
+    /// Flush buffered output and return the underlying writer.
+    pub fn finish(self) -> Result<W> {
+        match self {
+            RecordWriter::Fasta(w) => w.finish(),
+            RecordWriter::Fastq(w) => w.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn streams_wrapped_fasta_and_fastq() {
+        let fa = ">a desc\nACGT\n\nacgt\n>b\nNN-NN\n";
+        let recs: Vec<Contig> = RecordReader::new(Cursor::new(fa)).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(recs.len(), 2);
+        assert_eq!(recs[0].name, "a");
+        assert_eq!(recs[0].header, "a desc");
+        assert_eq!(recs[0].seq, b"ACGTACGT");
+        assert_eq!(recs[1].seq, b"NNNN");
+
+        let fq = "\n@r1 x\nAC\nGT\n+\nII\nII\n@r2\nA\n+\nI\n";
+        let mut rdr = RecordReader::new(Cursor::new(fq)).unwrap();
+        assert_eq!(rdr.format(), Format::Fastq);
+        let r1 = rdr.next_record().unwrap().unwrap();
+        assert_eq!((r1.name.as_str(), r1.seq.as_slice()), ("r1", &b"ACGT"[..]));
+        let r2 = rdr.next_record().unwrap().unwrap();
+        assert_eq!(r1.qual.as_deref(), Some(&b"IIII"[..]));
+        assert_eq!(r2.name, "r2");
+        assert!(rdr.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn detects_gzip_from_content_not_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reads.fq");
+        let mut gz = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        gz.write_all(b"@r1\nACGT\n+\nIIII\n").unwrap();
+        gz.finish().unwrap();
+
+        let recs = read_sequences(&path).unwrap();
+        assert_eq!(recs[0].seq, b"ACGT");
+    }
+
+    #[test]
+    fn every_codec_round_trips_by_content() {
+        let dir = tempfile::tempdir().unwrap();
+        for codec in [Codec::None, Codec::Gzip, Codec::Bgzf, Codec::Zstd, Codec::Bzip2, Codec::Xz] {
+            // Misleading extension on purpose: reading must rely on magic bytes
+            let path = dir.path().join(format!("{:?}.fa", codec));
+            let mut out = create_output_with(&path, Some(codec)).unwrap();
+            out.write_all(b">c1\nACGT\n").unwrap();
+            out.close().unwrap();
+
+            assert_eq!(sniff_codec(&path).unwrap(), codec);
+            assert_eq!(read_sequences(&path).unwrap()[0].seq, b"ACGT");
+        }
+        assert_eq!(Codec::from_extension(Path::new("x.fq.zst")), Codec::Zstd);
+        assert_eq!(Codec::from_extension(Path::new("x.fa")), Codec::None);
+    }
+
+    #[test]
+    fn raw_records_preserve_bytes_and_share_boundaries() {
+        let fa = ">a desc\nAC\nGT\n\n>b\nTT";
+        let raws: Vec<RawRecord> = RecordReader::new(Cursor::new(fa)).unwrap().raw_records().collect::<Result<_>>().unwrap();
+        assert_eq!(raws.len(), 2);
+        assert_eq!(raws[0].bytes, b">a desc\nAC\nGT\n\n");
+        assert_eq!(raws[0].parsed.seq, b"ACGT");
+        assert_eq!(raws[1].bytes, b">b\nTT\n");
+
+        let fq = "@r1\nAC\nGT\n+r1\nII\nII\n\n@r2\nA\n+\n#\n";
+        let raws: Vec<RawRecord> = RecordReader::new(Cursor::new(fq)).unwrap().raw_records().collect::<Result<_>>().unwrap();
+        assert_eq!(raws[0].bytes, b"@r1\nAC\nGT\n+r1\nII\nII\n");
+        assert_eq!(raws[1].bytes, b"@r2\nA\n+\n#\n");
+        assert_eq!(raws[1].parsed.qual.as_deref(), Some(&b"#"[..]));
+    }
+
+    #[test]
+    fn preserve_mode_keeps_case_and_gaps() {
+        let fa = ">a\nACgt-N*\nnn\n";
+        let norm = RecordReader::new(Cursor::new(fa)).unwrap().next_record().unwrap().unwrap();
+        assert_eq!(norm.seq, b"ACGTNNN");
+        let kept = RecordReader::new(Cursor::new(fa)).unwrap().with_mode(SeqMode::Preserve).next_record().unwrap().unwrap();
+        assert_eq!(kept.seq, b"ACgt-N*nn");
+
+        let fq = "@r\nac-T\n+\nABCD\n";
+        let rec = RecordReader::new(Cursor::new(fq)).unwrap().next_record().unwrap().unwrap();
+        assert_eq!((rec.seq.as_slice(), rec.qual.as_deref()), (&b"ACT"[..], Some(&b"ABD"[..])));
+    }
+
+    #[test]
+    fn strict_errors_carry_location_and_allow_resuming() {
+        let fa = ">ok\nACGT\n>bad desc\nACGT\nACXT\n>ok2\nAC\n";
+        let mut rdr = RecordReader::named(Cursor::new(fa), "ref.fa").unwrap().with_alphabet(Alphabet::Dna);
+        assert!(rdr.next_record().unwrap().is_some());
+        let err = rdr.next_record().unwrap_err();
+        let e = err.downcast_ref::<SeqError>().unwrap();
+        assert_eq!((e.kind, e.line, e.column, e.record.as_deref()), (SeqErrorKind::Symbol, 5, Some(3), Some("bad")));
+        assert_eq!(e.to_string(), "ref.fa:5:3: Invalid symbol 'X' for dna alphabet (record 'bad')");
+        assert_eq!(rdr.next_record().unwrap().unwrap().name, "ok2");
+
+        let fq = "@r1\nACGT\n+\nIIII\n@r2\nACGT\n+\nII";
+        let mut rdr = RecordReader::named(Cursor::new(fq), "reads.fq").unwrap();
+        rdr.next_record().unwrap();
+        let err = rdr.next_record().unwrap_err();
+        assert_eq!(err.to_string(), "reads.fq:8: Unexpected EOF while reading FASTQ quality (record 'r2')");
+    }
+
+    #[test]
+    fn fastq_round_trip_keeps_qualities() {
+        let mut w = RecordWriter::new(Vec::new(), Format::Fastq, 80);
+        w.write_record("r1 x", b"ACGT", Some(b"!#%I")).unwrap();
+        assert!(w.write_record("r2", b"A", None).is_err());
+        let out = w.finish().unwrap();
+        assert_eq!(out, b"@r1 x\nACGT\n+\n!#%I\n");
+
+        let rec = RecordReader::new(Cursor::new(out)).unwrap().next_record().unwrap().unwrap();
+        assert_eq!(rec.header, "r1 x");
+        assert_eq!(rec.qual.as_deref(), Some(&b"!#%I"[..]));
     }
-    Ok(())
 }
@@ -0,0 +1,334 @@
+//! Read simulation: sequencing errors and Phred qualities.
+//!
+//! An [`ErrorModel`] turns an error-free template (a sampled fragment) into a [`SimulatedRead`] carrying
+//! substitutions, insertions and deletions, Phred+33 qualities, and the exact list of [`ReadError`]s applied, so
+//! the truth can be written next to the reads.
+//!
+//! ### Profiles
+//! - [`ErrorProfile::Illumina`]: mostly substitutions; the error rate rises quadratically toward the 3' end and
+//!   qualities fall with it (default mean error rate 0.5%).
+//! - [`ErrorProfile::LongRead`]: indel-heavy errors spread evenly along the read, with uniformly low qualities
+//!   (default mean error rate 8%).
+//!
+//! Erroneous bases get low qualities (Q2–Q12); correct bases get the Phred value of their position's error rate,
+//! with a little jitter.
+//!
+//! ### Paired-end reads
+//! [`read_pair`] reads a fixed number of bases from both ends of an insert: R1 from the forward strand at its
+//! start, R2 from the reverse strand at its end.
+
+use crate::seqio::reverse_complement;
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use rand::prelude::*;
+
+const BASES: &[u8] = b"ACGT";
+
+/// Highest quality emitted (Phred 41, `J`).
+const MAX_QUAL: i32 = 41;
+
+/// Quality of every base of an error-free read (Phred 40, `I`).
+pub const PERFECT_QUAL: u8 = b'I';
+
+/// Sequencing error profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ErrorProfile {
+    /// Substitution-dominated errors that increase toward the 3' end
+    Illumina,
+    /// Indel-heavy errors spread evenly along the read
+    LongRead,
+}
+
+impl ErrorProfile {
+    /// Mean per-base error rate used when none is given.
+    pub fn default_rate(self) -> f64 {
+        match self {
+            ErrorProfile::Illumina => 0.005,
+            ErrorProfile::LongRead => 0.08,
+        }
+    }
+
+    /// Fractions of errors that are (substitutions, insertions); deletions take the rest.
+    fn mix(self) -> (f64, f64) {
+        match self {
+            ErrorProfile::Illumina => (0.95, 0.025),
+            ErrorProfile::LongRead => (0.3, 0.35),
+        }
+    }
+
+    /// Lower-case name, as accepted on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorProfile::Illumina => "illumina",
+            ErrorProfile::LongRead => "long-read",
+        }
+    }
+}
+
+/// Kind of a simulated sequencing error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    Substitution,
+    Insertion,
+    Deletion,
+}
+
+impl EditKind {
+    /// Short code used in truth files (`SUB`, `INS`, `DEL`).
+    pub fn code(self) -> &'static str {
+        match self {
+            EditKind::Substitution => "SUB",
+            EditKind::Insertion => "INS",
+            EditKind::Deletion => "DEL",
+        }
+    }
+}
+
+/// One error applied to a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadError {
+    pub kind: EditKind,
+    /// 0-based template position (for insertions, the template base the inserted base precedes)
+    pub template_pos: usize,
+    /// 0-based read position (for deletions, the read base that follows the deleted base)
+    pub read_pos: usize,
+    /// Template base (`None` for insertions)
+    pub template_base: Option<u8>,
+    /// Read base (`None` for deletions)
+    pub read_base: Option<u8>,
+}
+
+/// A read produced by [`ErrorModel::simulate`].
+#[derive(Debug, Clone)]
+pub struct SimulatedRead {
+    pub seq: Vec<u8>,
+    /// Phred+33 qualities, one per base of `seq`
+    pub qual: Vec<u8>,
+    /// Errors in read order
+    pub errors: Vec<ReadError>,
+}
+
+impl SimulatedRead {
+    /// Number of substitutions, insertions and deletions.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let n = |k: EditKind| self.errors.iter().filter(|e| e.kind == k).count();
+        (n(EditKind::Substitution), n(EditKind::Insertion), n(EditKind::Deletion))
+    }
+}
+
+/// Position-dependent error and quality model.
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorModel {
+    pub profile: ErrorProfile,
+    /// Mean per-base error probability
+    pub rate: f64,
+}
+
+impl ErrorModel {
+    /// Model for `profile` at its default error rate.
+    pub fn new(profile: ErrorProfile) -> Self {
+        ErrorModel { profile, rate: profile.default_rate() }
+    }
+
+    /// Override the mean per-base error rate (`0 <= rate < 0.5`).
+    pub fn with_rate(mut self, rate: f64) -> Result<Self> {
+        if !(0.0..0.5).contains(&rate) {
+            return Err(anyhow!("--error-rate must be in [0, 0.5)"));
+        }
+        self.rate = rate;
+        Ok(self)
+    }
+
+    /// Error probability at 0-based template position `pos` of a `len`-long template.
+    pub fn error_prob(&self, pos: usize, len: usize) -> f64 {
+        let p = match self.profile {
+            ErrorProfile::Illumina => {
+                // 0.4x the mean at the 5' end, 2.2x at the 3' end; averages to `rate`
+                let x = if len > 1 { pos as f64 / (len - 1) as f64 } else { 0.0 };
+                self.rate * (0.4 + 1.8 * x * x)
+            }
+            ErrorProfile::LongRead => self.rate,
+        };
+        p.min(0.75)
+    }
+
+    fn quality<R: Rng>(&self, p: f64, is_error: bool, rng: &mut R) -> u8 {
+        let q = if is_error {
+            rng.gen_range(2..=12)
+        } else {
+            let jitter = match self.profile {
+                ErrorProfile::Illumina => 2,
+                ErrorProfile::LongRead => 4,
+            };
+            (-10.0 * p.max(1e-5).log10()).round() as i32 + rng.gen_range(-jitter..=jitter)
+        };
+        (q.clamp(2, MAX_QUAL) + 33) as u8
+    }
+
+    /// Apply random errors to `template`, producing a read with qualities and its error list.
+    pub fn simulate<R: Rng>(&self, template: &[u8], rng: &mut R) -> SimulatedRead {
+        self.simulate_inner(template, None, rng)
+    }
+
+    /// Like [`ErrorModel::simulate`], but read only until the read is `read_len` bases long. Errors follow the
+    /// read position, so the profile's 3' end is the end of the read rather than of the template.
+    pub fn simulate_read<R: Rng>(&self, template: &[u8], read_len: usize, rng: &mut R) -> SimulatedRead {
+        self.simulate_inner(template, Some(read_len), rng)
+    }
+
+    fn simulate_inner<R: Rng>(&self, template: &[u8], read_len: Option<usize>, rng: &mut R) -> SimulatedRead {
+        let (sub_frac, ins_frac) = self.profile.mix();
+        let len = read_len.unwrap_or(template.len());
+        let mut read = SimulatedRead {
+            seq: Vec::with_capacity(len + 1),
+            qual: Vec::with_capacity(len + 1),
+            errors: Vec::new(),
+        };
+        for (i, &b) in template.iter().enumerate() {
+            if read_len.is_some_and(|n| read.seq.len() >= n) { break; }
+            let pos = if read_len.is_some() { read.seq.len() } else { i };
+            let p = self.error_prob(pos, len);
+            if rng.gen::<f64>() >= p {
+                read.seq.push(b);
+                read.qual.push(self.quality(p, false, rng));
+                continue;
+            }
+            let kind = rng.gen::<f64>();
+            if kind < sub_frac {
+                let alt = substitute(b, rng);
+                read.errors.push(ReadError {
+                    kind: EditKind::Substitution,
+                    template_pos: i,
+                    read_pos: read.seq.len(),
+                    template_base: Some(b),
+                    read_base: Some(alt),
+                });
+                read.seq.push(alt);
+                read.qual.push(self.quality(p, true, rng));
+            } else if kind < sub_frac + ins_frac {
+                let ins = match_case(BASES[rng.gen_range(0..4)], b);
+                read.errors.push(ReadError {
+                    kind: EditKind::Insertion,
+                    template_pos: i,
+                    read_pos: read.seq.len(),
+                    template_base: None,
+                    read_base: Some(ins),
+                });
+                read.seq.push(ins);
+                read.qual.push(self.quality(p, true, rng));
+                read.seq.push(b);
+                read.qual.push(self.quality(p, false, rng));
+            } else {
+                read.errors.push(ReadError {
+                    kind: EditKind::Deletion,
+                    template_pos: i,
+                    read_pos: read.seq.len(),
+                    template_base: Some(b),
+                    read_base: None,
+                });
+            }
+        }
+        if let Some(n) = read_len {
+            // An insertion at the last position can overshoot by the base it preceded
+            read.seq.truncate(n);
+            read.qual.truncate(n);
+        }
+        read
+    }
+}
+
+/// Error-free read of the first `read_len` bases of `template`, with [`PERFECT_QUAL`] qualities.
+pub fn perfect_read(template: &[u8], read_len: usize) -> SimulatedRead {
+    let seq = template[..read_len.min(template.len())].to_vec();
+    SimulatedRead { qual: vec![PERFECT_QUAL; seq.len()], seq, errors: Vec::new() }
+}
+
+/// Read-name style for paired reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PairNaming {
+    /// `name/1` and `name/2`
+    Slash,
+    /// `name 1:N:0:1` and `name 2:N:0:1` (Casava 1.8+)
+    Casava,
+}
+
+impl PairNaming {
+    /// Header prefix of mate `mate` (1 or 2) of read `name`.
+    pub fn read_name(self, name: &str, mate: u8) -> String {
+        match self {
+            PairNaming::Slash => format!("{}/{}", name, mate),
+            PairNaming::Casava => format!("{} {}:N:0:1", name, mate),
+        }
+    }
+}
+
+/// Reads of `read_len` bases from both ends of `insert`: R1 from the forward strand starting at the first base, R2
+/// from the reverse strand starting at the last base. Errors are applied with `model` when given.
+pub fn read_pair<R: Rng>(
+    insert: &[u8],
+    read_len: usize,
+    model: Option<&ErrorModel>,
+    rng: &mut R,
+) -> (SimulatedRead, SimulatedRead) {
+    let rc = reverse_complement(insert);
+    let read = |template: &[u8], rng: &mut R| match model {
+        Some(m) => m.simulate_read(template, read_len, rng),
+        None => perfect_read(template, read_len),
+    };
+    let r1 = read(insert, rng);
+    let r2 = read(&rc, rng);
+    (r1, r2)
+}
+
+/// `base` with the case of `like`.
+fn match_case(base: u8, like: u8) -> u8 {
+    if like.is_ascii_lowercase() { base.to_ascii_lowercase() } else { base }
+}
+
+/// A random nucleotide different from `b` (any nucleotide if `b` is ambiguous), keeping its case.
+fn substitute<R: Rng>(b: u8, rng: &mut R) -> u8 {
+    let alt = match BASES.iter().position(|&x| x == b.to_ascii_uppercase()) {
+        Some(i) => BASES[(i + rng.gen_range(1..4)) % 4],
+        None => BASES[rng.gen_range(0..4)],
+    };
+    match_case(alt, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_errors_explain_the_read() {
+        let template: Vec<u8> = (0..5000).map(|i| BASES[(i * 7 + i / 3) % 4]).collect();
+        let mut rng = StdRng::seed_from_u64(11);
+
+        let clean = ErrorModel::new(ErrorProfile::Illumina).with_rate(0.0).unwrap().simulate(&template, &mut rng);
+        assert_eq!(clean.seq, template);
+        assert!(clean.qual.iter().all(|&q| q >= b'!' + 30));
+
+        let read = ErrorModel::new(ErrorProfile::LongRead).simulate(&template, &mut rng);
+        let (subs, ins, dels) = read.counts();
+        assert!(ins > 50 && dels > 50 && subs > 20);
+        assert_eq!(read.seq.len(), template.len() + ins - dels);
+        assert_eq!(read.qual.len(), read.seq.len());
+        for e in &read.errors {
+            if let Some(t) = e.template_base { assert_eq!(template[e.template_pos], t); }
+            if let Some(r) = e.read_base { assert_eq!(read.seq[e.read_pos], r); }
+        }
+    }
+
+    #[test]
+    fn mates_come_from_opposite_ends_and_strands() {
+        let insert = b"AACCGGTTACGTTTTT";
+        let mut rng = StdRng::seed_from_u64(3);
+        let (r1, r2) = read_pair(insert, 5, None, &mut rng);
+        assert_eq!((r1.seq.as_slice(), r2.seq.as_slice()), (&b"AACCG"[..], &b"AAAAA"[..]));
+        assert_eq!(r1.qual, vec![PERFECT_QUAL; 5]);
+
+        let model = ErrorModel::new(ErrorProfile::LongRead).with_rate(0.3).unwrap();
+        let (r1, r2) = read_pair(insert, 8, Some(&model), &mut rng);
+        assert!(r1.seq.len() <= 8 && r2.seq.len() <= 8);
+        assert_eq!(PairNaming::Casava.read_name("seq000001", 2), "seq000001 2:N:0:1");
+    }
+}
@@ -1,14 +1,15 @@
 //! Header reducer (`strip`).
 //!
 //! Accepts a **single FASTA** (plain or `.gz`) and writes a FASTA where each header is reduced to just
-//! the **accession** (first whitespace‑separated token). Sequence content is unchanged.
+//! the **accession** (first whitespace‑separated token). Sequence content is unchanged. FASTQ input is written
+//! back as FASTQ with its qualities.
 //!
 //! ### Example
 //! ```text
 //! limpet strip --input reference.fa.gz --output reference_accessions.fa
 //! ```
 
-use crate::seqio::{read_sequences, write_fasta, FastaRecord};
+use crate::seqio::{create_output_with, Codec, Contig, RecordReader, RecordWriter, SeqMode};
 use anyhow::{anyhow, Context, Result};
 use clap::Args;
 use std::path::PathBuf;
@@ -16,33 +17,49 @@ use std::path::PathBuf;
 /// Strip FASTA headers down to just the accession (first token), preserving sequences.
 #[derive(Args, Debug, Clone)]
 pub struct StripArgs {
-    /// Input FASTA (optionally gzipped). FASTQ is not intended for this command.
+    /// Input FASTA (optionally gzipped; `-` for stdin). FASTQ input produces FASTQ output.
     #[arg(short = 'i', long = "input", value_name = "FASTA")]
     pub input: PathBuf,
 
-    /// Output FASTA path
+    /// Output path (FASTA, or FASTQ when the input is FASTQ; `-` for stdout)
     #[arg(short = 'o', long = "output", value_name = "FASTA")]
     pub output: PathBuf,
+
+    /// Output compression (default: from the output file extension; plain for stdout)
+    #[arg(long = "compress", value_enum, value_name = "CODEC")]
+    pub compress: Option<Codec>,
+
+    /// Keep original case and non-letter symbols (soft-masking, `-` gaps) instead of upper-casing
+    #[arg(long = "keep-case")]
+    pub keep_case: bool,
+}
+
+/// Reduce a record's header to its accession token.
+pub fn strip_header(mut rec: Contig) -> Contig {
+    rec.header = rec.name.clone();
+    rec
 }
 
 /// Execute the `strip` subcommand.
-/// Reduces headers to accession tokens and writes FASTA.
+/// Streams records, reduces headers to accession tokens and writes them in the input format.
 pub fn run(args: StripArgs) -> Result<()> {
-    // Load sequences (FASTA or FASTA.GZ). read_sequences will also parse FASTQ, but this
-    // command is intended for FASTA; we simply use the accession token `name` for headers.
-    let records = read_sequences(&args.input)
-        .with_context(|| format!("Failed to read {}", args.input.display()))?;
+    // Stream sequences (FASTA or FASTA.GZ). FASTQ is passed through as FASTQ;
+    // either way we simply use the accession token `name` for headers.
+    let mode = if args.keep_case { SeqMode::Preserve } else { SeqMode::Normalize };
+    let reader = RecordReader::from_path(&args.input)?.with_mode(mode);
+    let mut writer = RecordWriter::new(create_output_with(&args.output, args.compress)?, reader.format(), 80);
 
-    if records.is_empty() {
-        return Err(anyhow!("No sequences found in {}", args.input.display()));
+    let mut count = 0usize;
+    for rec in reader {
+        let c = strip_header(rec.with_context(|| format!("Failed to read {}", args.input.display()))?);
+        writer.write_record(&c.header, &c.seq, c.qual.as_deref())?;
+        count += 1;
     }
+    writer.finish()?.close()?;
 
-    let out: Vec<FastaRecord<'_>> = records
-        .iter()
-        .map(|c| FastaRecord { header: c.name.clone(), seq: c.seq.as_slice() })
-        .collect();
-
-    write_fasta(&out, &args.output, 80)?;
-    eprintln!("Wrote {} sequences to {}", out.len(), args.output.display());
+    if count == 0 {
+        return Err(anyhow!("No sequences found in {}", args.input.display()));
+    }
+    eprintln!("Wrote {} sequences to {}", count, args.output.display());
     Ok(())
 }
@@ -0,0 +1,253 @@
+//! Input validator (`validate`).
+//!
+//! Checks one or more **FASTA/FASTQ** files (optionally compressed) against a sequence [`Alphabet`] using the strict
+//! mode of [`RecordReader`], and prints a **JSON report**: per file, the detected format, record and base counts, and
+//! every problem found with its line number, column and record name. Symbol and quality problems do not stop the
+//! scan; structural problems (e.g. a truncated FASTQ record) end the scan of that file.
+//!
+//! The command exits with an error if any file is invalid, so it can gate pipelines.
+//!
+//! ### Example
+//! ```text
+//! limpet validate reads_R1.fq.gz reads_R2.fq.gz --alphabet dna -o report.json
+//! ```
+
+use crate::seqio::{create_output, Alphabet, Format, RecordReader, SeqError, SeqErrorKind, SeqMode};
+use anyhow::{anyhow, Result};
+use clap::Args;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Validate FASTA/FASTQ files against an alphabet and report problems with their locations.
+#[derive(Args, Debug, Clone)]
+pub struct ValidateArgs {
+    /// One or more input files (FASTA/FASTQ, optionally compressed); `-` reads stdin
+    #[arg(value_name = "INPUT", required = true)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Alphabet that sequence symbols must belong to
+    #[arg(long = "alphabet", value_enum, default_value = "dna")]
+    pub alphabet: Alphabet,
+
+    /// Maximum number of problems listed per file (all are counted)
+    #[arg(long = "max-errors", value_name = "INT", default_value_t = 20)]
+    pub max_errors: usize,
+
+    /// Report path (JSON); `-` writes stdout
+    #[arg(short = 'o', long = "output", value_name = "JSON", default_value = "-")]
+    pub output: PathBuf,
+}
+
+/// Validation outcome for a single input.
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    /// Input name (file path or `stdin`)
+    pub source: String,
+    /// Detected format, if the input could be opened and recognised
+    pub format: Option<Format>,
+    pub alphabet: Alphabet,
+    /// Records read (including invalid ones that could be skipped)
+    pub records: u64,
+    /// Sequence symbols read
+    pub bases: u64,
+    /// Total number of problems found
+    pub error_count: usize,
+    /// The first `max_errors` problems
+    pub errors: Vec<SeqError>,
+}
+
+impl FileReport {
+    pub fn is_valid(&self) -> bool {
+        self.error_count == 0
+    }
+
+    fn push(&mut self, err: SeqError, max_errors: usize) {
+        self.error_count += 1;
+        if self.errors.len() < max_errors {
+            self.errors.push(err);
+        }
+    }
+}
+
+/// Turn any error into a [`SeqError`] (I/O and decoder errors get line 0, meaning unknown).
+fn as_seq_error(err: anyhow::Error, source: &str) -> SeqError {
+    match err.downcast::<SeqError>() {
+        Ok(e) => e,
+        Err(other) => SeqError {
+            kind: SeqErrorKind::Format,
+            source: source.to_string(),
+            line: 0,
+            column: None,
+            record: None,
+            message: format!("{:#}", other),
+        },
+    }
+}
+
+/// Validate every record of an opened reader.
+pub fn validate_reader<R: BufRead>(reader: RecordReader<R>, alphabet: Alphabet, max_errors: usize) -> FileReport {
+    let mut reader = reader.with_mode(SeqMode::Preserve).with_alphabet(alphabet);
+    let mut report = FileReport {
+        source: reader.source().to_string(),
+        format: Some(reader.format()),
+        alphabet,
+        records: 0,
+        bases: 0,
+        error_count: 0,
+        errors: Vec::new(),
+    };
+    loop {
+        match reader.next_record() {
+            Ok(Some(rec)) => {
+                report.records += 1;
+                report.bases += rec.seq.len() as u64;
+            }
+            Ok(None) => break,
+            Err(err) => {
+                let e = as_seq_error(err, &report.source);
+                let recoverable = e.is_recoverable();
+                if recoverable {
+                    report.records += 1;
+                }
+                report.push(e, max_errors);
+                if !recoverable { break; }
+            }
+        }
+    }
+    report
+}
+
+/// Open and validate one path; failures to open or recognise the file are reported as problems.
+pub fn validate_path(path: &Path, alphabet: Alphabet, max_errors: usize) -> FileReport {
+    match RecordReader::from_path(path) {
+        Ok(reader) => validate_reader(reader, alphabet, max_errors),
+        Err(err) => {
+            let source = path.display().to_string();
+            let mut report = FileReport {
+                source: source.clone(),
+                format: None,
+                alphabet,
+                records: 0,
+                bases: 0,
+                error_count: 0,
+                errors: Vec::new(),
+            };
+            report.push(as_seq_error(err, &source), max_errors);
+            report
+        }
+    }
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_opt<T: ToString>(v: Option<T>) -> String {
+    v.map(|x| x.to_string()).unwrap_or_else(|| "null".to_string())
+}
+
+/// Render reports as a JSON document.
+pub fn reports_to_json(reports: &[FileReport]) -> String {
+    let mut out = String::new();
+    let valid = reports.iter().all(|r| r.is_valid());
+    out.push_str(&format!("{{\n  \"valid\": {},\n  \"files\": [", valid));
+    for (i, r) in reports.iter().enumerate() {
+        if i > 0 { out.push(','); }
+        let format = r.format.map(|f| match f {
+            Format::Fasta => json_str("fasta"),
+            Format::Fastq => json_str("fastq"),
+        });
+        out.push_str(&format!(
+            "\n    {{\n      \"file\": {},\n      \"format\": {},\n      \"alphabet\": {},\n      \"valid\": {},\n      \"records\": {},\n      \"bases\": {},\n      \"error_count\": {},\n      \"errors\": [",
+            json_str(&r.source),
+            json_opt(format),
+            json_str(r.alphabet.name()),
+            r.is_valid(),
+            r.records,
+            r.bases,
+            r.error_count
+        ));
+        for (j, e) in r.errors.iter().enumerate() {
+            if j > 0 { out.push(','); }
+            let kind = match e.kind {
+                SeqErrorKind::Format => "format",
+                SeqErrorKind::Symbol => "symbol",
+                SeqErrorKind::Quality => "quality",
+            };
+            out.push_str(&format!(
+                "\n        {{\"kind\": {}, \"line\": {}, \"column\": {}, \"record\": {}, \"message\": {}}}",
+                json_str(kind),
+                if e.line == 0 { "null".to_string() } else { e.line.to_string() },
+                json_opt(e.column),
+                json_opt(e.record.as_deref().map(json_str)),
+                json_str(&e.message)
+            ));
+        }
+        if !r.errors.is_empty() { out.push_str("\n      "); }
+        out.push_str("]\n    }");
+    }
+    if !reports.is_empty() { out.push_str("\n  "); }
+    out.push_str("]\n}\n");
+    out
+}
+
+/// Execute the `validate` subcommand.
+/// Validates each input, writes the JSON report, and fails if any input is invalid.
+pub fn run(args: ValidateArgs) -> Result<()> {
+    let reports: Vec<FileReport> = args
+        .inputs
+        .iter()
+        .map(|p| validate_path(p, args.alphabet, args.max_errors))
+        .collect();
+
+    let mut out = create_output(&args.output)?;
+    out.write_all(reports_to_json(&reports).as_bytes())?;
+    out.close()?;
+
+    for r in &reports {
+        if let Some(first) = r.errors.first() {
+            eprintln!("{}: INVALID ({} problem(s)); first: {}", r.source, r.error_count, first);
+        } else {
+            eprintln!("{}: OK ({} records, {} bases)", r.source, r.records, r.bases);
+        }
+    }
+    let failed = reports.iter().filter(|r| !r.is_valid()).count();
+    if failed > 0 {
+        return Err(anyhow!("{} of {} file(s) failed validation", failed, reports.len()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn reports_every_bad_record() {
+        let fa = ">a\nACGT\n>b\nACUT\n>c\nAC\nGT\n>d\nNNZ\n";
+        let reader = RecordReader::named(Cursor::new(fa), "x.fa").unwrap();
+        let report = validate_reader(reader, Alphabet::Dna, 1);
+        assert_eq!(report.records, 4);
+        assert_eq!(report.error_count, 2);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!((report.errors[0].line, report.errors[0].record.as_deref()), (4, Some("b")));
+
+        let json = reports_to_json(&[report]);
+        assert!(json.contains("\"valid\": false"));
+        assert!(json.contains("\"kind\": \"symbol\", \"line\": 4, \"column\": 3, \"record\": \"b\""));
+    }
+}
@@ -0,0 +1,474 @@
+//! Minimal **VCF** reader and haplotype builder.
+//!
+//! Reads the fixed VCF columns (`CHROM POS ID REF ALT`) and the `GT` field of one sample. Sequence alleles (SNPs,
+//! MNPs and indels with their anchor base) are kept; symbolic (`<DEL>`), breakend and `*` alleles are skipped and
+//! counted. Positions are **1-based** in the file and 0-based in [`Variant::pos`].
+//!
+//! A [`Haplotype`] is a reference contig with the alleles of one genotype slot applied. It is not materialised: bases
+//! are fetched from the [`Reference`] and spliced with the alternate alleles on demand, so an indexed 3 Gbp reference
+//! keeps its flat memory. Haplotype coordinates map back to the reference through [`Haplotype::blocks`], and a
+//! fragment sampled from a haplotype is reported against the reference with [`Haplotype::to_reference`].
+//!
+//! ### Genotypes
+//! With sample columns, haplotype *h* carries allele `GT[h]` of every record (`0|1` puts the alternate allele on the
+//! second haplotype only, `1/1` on both; `.` and `0` leave the reference). Phasing (`|` vs `/`) is not
+//! distinguished. A sites-only VCF applies the first alternate allele to a single haplotype.
+
+use crate::faidx::Reference;
+use crate::fragment::{Fragment, LiftBlock, Origin};
+use crate::seqio::open_input;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+
+/// One VCF record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    pub chrom: String,
+    /// 0-based position of the first REF base
+    pub pos: u64,
+    /// ID column, if not `.`
+    pub id: Option<String>,
+    /// Reference allele (upper case)
+    pub ref_allele: Vec<u8>,
+    /// Alternate alleles (upper case)
+    pub alts: Vec<Vec<u8>>,
+    /// Allele index per haplotype from the sample's `GT` (`None` for `.`); empty for sites-only records
+    pub genotype: Vec<Option<usize>>,
+}
+
+impl Variant {
+    /// The record's ID, or `POS:REF>ALT` (1-based) for the given allele.
+    pub fn label(&self, allele: usize) -> String {
+        match &self.id {
+            Some(id) => id.clone(),
+            None => format!(
+                "{}:{}>{}",
+                self.pos + 1,
+                String::from_utf8_lossy(&self.ref_allele),
+                String::from_utf8_lossy(&self.alts[allele - 1])
+            ),
+        }
+    }
+}
+
+/// Records of a VCF file for one sample.
+#[derive(Debug, Clone)]
+pub struct Vcf {
+    pub variants: Vec<Variant>,
+    /// Sample whose genotypes were read, if the file has samples
+    pub sample: Option<String>,
+    /// Number of haplotypes: the longest `GT`, or 1 for a sites-only VCF
+    pub ploidy: usize,
+    /// Records skipped for having symbolic or breakend alleles
+    pub skipped: usize,
+}
+
+fn is_sequence_allele(a: &str) -> bool {
+    !a.is_empty() && a.bytes().all(|b| b"ACGTNacgtn".contains(&b))
+}
+
+/// Parse a `GT` value such as `0|1`, `1/1`, `1` or `./.`.
+fn parse_gt(gt: &str, line_no: usize) -> Result<Vec<Option<usize>>> {
+    gt.split(['|', '/'])
+        .map(|a| match a {
+            "." => Ok(None),
+            _ => a.parse().map(Some).map_err(|_| anyhow!("line {}: invalid GT '{}'", line_no, gt)),
+        })
+        .collect()
+}
+
+/// Parse one VCF data line, reading the genotype from sample column `sample_col` (0-based column index).
+/// Returns `None` for records with symbolic or breakend alleles.
+pub fn parse_vcf_line(line: &str, line_no: usize, sample_col: Option<usize>) -> Result<Option<Variant>> {
+    let cols: Vec<&str> = line.split('\t').collect();
+    if cols.len() < 5 {
+        return Err(anyhow!("line {}: expected at least 5 tab-separated VCF columns", line_no));
+    }
+    let pos: u64 = cols[1].parse().map_err(|_| anyhow!("line {}: invalid POS '{}'", line_no, cols[1]))?;
+    if pos == 0 {
+        return Err(anyhow!("line {}: POS is 1-based", line_no));
+    }
+    let alts: Vec<&str> = cols[4].split(',').collect();
+    if !is_sequence_allele(cols[3]) || !alts.iter().all(|a| is_sequence_allele(a)) {
+        return Ok(None);
+    }
+    let genotype = match sample_col {
+        Some(c) => {
+            let gt_index = cols.get(8).and_then(|f| f.split(':').position(|k| k == "GT"));
+            let value = gt_index.and_then(|g| cols.get(c)?.split(':').nth(g));
+            match value {
+                Some(v) => parse_gt(v, line_no)?,
+                None => return Err(anyhow!("line {}: no GT value for the sample", line_no)),
+            }
+        }
+        None => Vec::new(),
+    };
+    if let Some(a) = genotype.iter().flatten().find(|&&a| a > alts.len()) {
+        return Err(anyhow!("line {}: GT allele {} but only {} ALT allele(s)", line_no, a, alts.len()));
+    }
+    Ok(Some(Variant {
+        chrom: cols[0].to_string(),
+        pos: pos - 1,
+        id: Some(cols[2]).filter(|id| !id.is_empty() && *id != ".").map(String::from),
+        ref_allele: cols[3].to_ascii_uppercase().into_bytes(),
+        alts: alts.iter().map(|a| a.to_ascii_uppercase().into_bytes()).collect(),
+        genotype,
+    }))
+}
+
+/// Read a VCF file, taking genotypes from `sample` (default: the first sample column, if any).
+pub fn read_vcf<P: AsRef<Path>>(path: P, sample: Option<&str>) -> Result<Vcf> {
+    let path = path.as_ref();
+    let mut vcf = Vcf { variants: Vec::new(), sample: None, ploidy: 1, skipped: 0 };
+    let mut sample_col = None;
+    for (i, line) in open_input(path)?.lines().enumerate() {
+        let line = line?;
+        if line.starts_with("##") || line.trim().is_empty() { continue; }
+        if let Some(header) = line.strip_prefix('#') {
+            let cols: Vec<&str> = header.split('\t').collect();
+            sample_col = match sample {
+                Some(s) => Some(
+                    cols.iter()
+                        .skip(9)
+                        .position(|c| *c == s)
+                        .map(|p| p + 9)
+                        .ok_or_else(|| anyhow!("{}: no sample '{}' in the VCF header", path.display(), s))?,
+                ),
+                None => (cols.len() > 9).then_some(9),
+            };
+            vcf.sample = sample_col.map(|c| cols[c].to_string());
+            continue;
+        }
+        match parse_vcf_line(&line, i + 1, sample_col).map_err(|e| anyhow!("{}: {}", path.display(), e))? {
+            Some(v) => {
+                vcf.ploidy = vcf.ploidy.max(v.genotype.len());
+                vcf.variants.push(v);
+            }
+            None => vcf.skipped += 1,
+        }
+    }
+    Ok(vcf)
+}
+
+/// An applied allele, in reference and haplotype coordinates.
+#[derive(Debug, Clone)]
+struct Edit {
+    ref_pos: usize,
+    ref_len: usize,
+    alt: Vec<u8>,
+    hap_pos: usize,
+    label: String,
+}
+
+/// A stretch of haplotype sequence: reference bases from the given position, or the allele of an edit.
+#[derive(Debug, Clone, Copy)]
+enum Piece {
+    Ref(usize),
+    Alt(usize),
+}
+
+/// One haplotype of a contig: the reference with one genotype slot's alleles applied.
+#[derive(Debug, Clone)]
+pub struct Haplotype {
+    /// Index of the contig in the reference
+    pub contig: usize,
+    /// Genotype slot (0-based)
+    pub index: usize,
+    ref_len: usize,
+    len: usize,
+    edits: Vec<Edit>,
+}
+
+impl Haplotype {
+    /// Apply `(variant, allele)` pairs (sorted by position) to a contig of `ref_len` bases. Variants overlapping an
+    /// earlier applied one are left out; their number is returned alongside.
+    pub fn build<'a, I>(contig: usize, index: usize, ref_len: usize, alleles: I) -> (Haplotype, usize)
+    where
+        I: IntoIterator<Item = (&'a Variant, usize)>,
+    {
+        let mut edits: Vec<Edit> = Vec::new();
+        let (mut shift, mut overlapping) = (0isize, 0usize);
+        for (v, allele) in alleles {
+            let ref_pos = v.pos as usize;
+            if edits.last().is_some_and(|e| ref_pos < e.ref_pos + e.ref_len) {
+                overlapping += 1;
+                continue;
+            }
+            let alt = v.alts[allele - 1].clone();
+            let hap_pos = (ref_pos as isize + shift) as usize;
+            shift += alt.len() as isize - v.ref_allele.len() as isize;
+            edits.push(Edit { ref_pos, ref_len: v.ref_allele.len(), alt, hap_pos, label: v.label(allele) });
+        }
+        let len = (ref_len as isize + shift) as usize;
+        (Haplotype { contig, index, ref_len, len, edits }, overlapping)
+    }
+
+    /// Length in bp.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// True if the haplotype is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of applied alleles.
+    pub fn edits(&self) -> usize {
+        self.edits.len()
+    }
+
+    /// Pieces covering haplotype interval `[start, end)`, as `(hap_start, hap_end, piece)`.
+    fn pieces(&self, start: usize, end: usize) -> Vec<(usize, usize, Piece)> {
+        let mut out = Vec::new();
+        let mut k = self.edits.partition_point(|e| e.hap_pos + e.alt.len() <= start);
+        let mut p = start;
+        while p < end {
+            match self.edits.get(k) {
+                Some(e) if e.hap_pos <= p => {
+                    let stop = end.min(e.hap_pos + e.alt.len());
+                    out.push((p, stop, Piece::Alt(k)));
+                    p = stop;
+                    k += 1;
+                }
+                next => {
+                    // Reference bases keep a constant offset up to the next edit
+                    let (stop, ref_pos) = match next {
+                        Some(e) => (end.min(e.hap_pos), p + e.ref_pos - e.hap_pos),
+                        None => (end, p + self.ref_len - self.len),
+                    };
+                    out.push((p, stop, Piece::Ref(ref_pos)));
+                    p = stop;
+                }
+            }
+        }
+        out
+    }
+
+    /// Bases `[start, end)` (0-based haplotype coordinates).
+    pub fn fetch(&self, reference: &mut Reference, start: usize, end: usize) -> Result<Vec<u8>> {
+        let mut seq = Vec::with_capacity(end.saturating_sub(start));
+        for (s, e, piece) in self.pieces(start, end) {
+            match piece {
+                Piece::Ref(r) => seq.extend(reference.fetch(self.contig, r, r + e - s)?),
+                Piece::Alt(k) => {
+                    let edit = &self.edits[k];
+                    seq.extend_from_slice(&edit.alt[s - edit.hap_pos..e - edit.hap_pos]);
+                }
+            }
+        }
+        Ok(seq)
+    }
+
+    /// Reference origin of the bases of haplotype interval `[start, end)`, with offsets relative to `start`.
+    pub fn blocks(&self, start: usize, end: usize) -> Vec<LiftBlock> {
+        self.pieces(start, end)
+            .into_iter()
+            .map(|(s, e, piece)| match piece {
+                Piece::Ref(r) => LiftBlock { offset: s - start, ref_pos: r, ref_len: e - s },
+                Piece::Alt(k) => {
+                    let edit = &self.edits[k];
+                    let into = (s - edit.hap_pos).min(edit.ref_len - 1);
+                    LiftBlock { offset: s - start, ref_pos: edit.ref_pos + into, ref_len: edit.ref_len - into }
+                }
+            })
+            .collect()
+    }
+
+    /// Labels of the alleles overlapping haplotype interval `[start, end)`.
+    pub fn labels(&self, start: usize, end: usize) -> Vec<String> {
+        self.pieces(start, end)
+            .into_iter()
+            .filter_map(|(_, _, piece)| match piece {
+                Piece::Alt(k) => Some(self.edits[k].label.clone()),
+                Piece::Ref(_) => None,
+            })
+            .collect()
+    }
+
+    /// Haplotype position of 0-based reference position `pos` (a position inside a replaced allele maps into it).
+    pub fn lift(&self, pos: usize) -> usize {
+        let k = self.edits.partition_point(|e| e.ref_pos <= pos);
+        match k.checked_sub(1).map(|k| &self.edits[k]) {
+            None => pos,
+            Some(e) if pos < e.ref_pos + e.ref_len => e.hap_pos + (pos - e.ref_pos).min(e.alt.len()),
+            Some(e) => pos + e.hap_pos + e.alt.len() - e.ref_pos - e.ref_len,
+        }
+        .min(self.len)
+    }
+
+    /// Fragment `f` sampled from this haplotype, in reference coordinates: `start`/`end` are lifted back, the
+    /// overlapping alleles recorded and the bases kept. On a circular haplotype `f.end` may pass [`Haplotype::len`];
+    /// the interval then continues from the origin.
+    pub fn to_reference(&self, reference: &Reference, f: Fragment) -> Fragment {
+        let end = f.end.min(self.len);
+        let (mut lift, mut variants) = (self.blocks(f.start, end), self.labels(f.start, end));
+        if f.end > self.len {
+            let wrapped = self.len - f.start;
+            lift.extend(self.blocks(0, f.end - self.len).into_iter().map(|b| LiftBlock { offset: b.offset + wrapped, ..b }));
+            variants.extend(self.labels(0, f.end - self.len));
+        }
+        let mut frag = Fragment {
+            contig: reference.name(self.contig).to_string(),
+            start: lift[0].ref_pos,
+            end: 0,
+            reverse: f.reverse,
+            circular_len: f.circular_len.map(|_| reference.seq_len(self.contig)),
+            origin: Origin::Haplotype { index: self.index + 1, variants, lift },
+            seq: f.seq,
+            umi: f.umi,
+        };
+        frag.end = frag.ref_pos(frag.seq.len() - 1);
+        frag
+    }
+}
+
+/// Build `vcf.ploidy` haplotypes for every contig of `reference` (contig-major order).
+///
+/// Each REF allele is checked against the reference. Returns the haplotypes and the number of variants left out:
+/// on contigs missing from the reference, or overlapping an earlier variant on the same haplotype.
+pub fn build_haplotypes(reference: &mut Reference, vcf: &Vcf) -> Result<(Vec<Haplotype>, usize)> {
+    let mut by_contig: HashMap<usize, Vec<&Variant>> = HashMap::new();
+    let mut left_out = 0;
+    for v in &vcf.variants {
+        let Some(i) = reference.position(&v.chrom) else {
+            left_out += 1;
+            continue;
+        };
+        let (start, end) = (v.pos as usize, v.pos as usize + v.ref_allele.len());
+        if end > reference.seq_len(i) {
+            return Err(anyhow!("VCF record {}:{} runs past the end of '{}'", v.chrom, v.pos + 1, v.chrom));
+        }
+        let bases = reference.fetch(i, start, end).with_context(|| format!("Checking VCF record {}:{}", v.chrom, v.pos + 1))?;
+        if !bases.eq_ignore_ascii_case(&v.ref_allele) {
+            return Err(anyhow!(
+                "VCF REF {} at {}:{} does not match the reference ({})",
+                String::from_utf8_lossy(&v.ref_allele),
+                v.chrom,
+                v.pos + 1,
+                String::from_utf8_lossy(&bases)
+            ));
+        }
+        by_contig.entry(i).or_default().push(v);
+    }
+    let mut out = Vec::with_capacity(reference.len() * vcf.ploidy);
+    for i in 0..reference.len() {
+        let mut variants = by_contig.remove(&i).unwrap_or_default();
+        variants.sort_by_key(|v| v.pos);
+        for h in 0..vcf.ploidy {
+            let alleles = variants.iter().filter_map(|v| {
+                let allele = if v.genotype.is_empty() { Some(1) } else { v.genotype.get(h).copied().flatten() };
+                allele.filter(|&a| a > 0).map(|a| (*v, a))
+            });
+            let (hap, overlapping) = Haplotype::build(i, h, reference.seq_len(i), alleles);
+            left_out += overlapping;
+            out.push(hap);
+        }
+    }
+    Ok((out, left_out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seqio::Contig;
+
+    #[test]
+    fn haplotypes_apply_genotyped_alleles() {
+        let lines = [
+            "c\t3\trs1\tG\tT\t.\tPASS\t.\tGT\t0|1",
+            "c\t5\t.\tA\tACCC\t.\tPASS\t.\tGT\t1|1",
+            "c\t7\t.\tGTA\tG\t.\tPASS\t.\tGT\t1/0",
+            "c\t9\t.\tT\t<DEL>\t.\tPASS\t.\tGT\t1|1",
+        ];
+        let parsed: Vec<Option<Variant>> = lines.iter().enumerate().map(|(i, l)| parse_vcf_line(l, i + 1, Some(9)).unwrap()).collect();
+        assert!(parsed[3].is_none());
+        let vcf = Vcf { variants: parsed.into_iter().flatten().collect(), sample: Some("s".into()), ploidy: 2, skipped: 1 };
+
+        //                                        123456789012
+        let contigs = vec![Contig { name: "c".into(), header: "c".into(), seq: b"ACGTACGTACGT".to_vec(), qual: None }];
+        let mut reference = Reference::Loaded(contigs);
+        let (haps, left_out) = build_haplotypes(&mut reference, &vcf).unwrap();
+        assert_eq!(left_out, 0);
+        let seq = |h: &Haplotype, r: &mut Reference| h.fetch(r, 0, h.len()).unwrap();
+        assert_eq!(seq(&haps[0], &mut reference), b"ACGTACCCCGCGT");
+        assert_eq!(seq(&haps[1], &mut reference), b"ACTTACCCCGTACGT");
+        assert_eq!(haps[1].labels(2, 6), ["rs1", "5:A>ACCC"]);
+
+        // Inserted bases map to their anchor; bases after the insertion map back with the original offset
+        let blocks = haps[1].blocks(3, 10);
+        assert_eq!(blocks[0], LiftBlock { offset: 0, ref_pos: 3, ref_len: 1 });
+        assert_eq!(blocks[1], LiftBlock { offset: 1, ref_pos: 4, ref_len: 1 });
+        assert_eq!(blocks[2], LiftBlock { offset: 5, ref_pos: 5, ref_len: 2 });
+        assert_eq!(haps[1].lift(7), 10);
+
+        let bad = Vcf { variants: vec![parse_vcf_line("c\t1\t.\tG\tT", 1, None).unwrap().unwrap()], ..vcf };
+        assert!(build_haplotypes(&mut reference, &bad).is_err());
+    }
+
+    #[test]
+    fn haplotype_fragments_map_back_to_the_reference() {
+        let seq = b"ACGTTGCAACGTTGCAACGTTGCAACGTTGCA".to_vec();
+        let contigs = vec![Contig { name: "c".into(), header: "c".into(), seq: seq.clone(), qual: None }];
+        let mut reference = Reference::Loaded(contigs);
+        let lines = ["c\t5\tsnp\tT\tA\t.\t.\t.\tGT\t1|0", "c\t12\tins\tT\tTGGG\t.\t.\t.\tGT\t0|1"];
+        let variants = lines.iter().map(|l| parse_vcf_line(l, 1, Some(9)).unwrap().unwrap()).collect();
+        let vcf = Vcf { variants, sample: Some("s".into()), ploidy: 2, skipped: 0 };
+        let (haps, _) = build_haplotypes(&mut reference, &vcf).unwrap();
+
+        let mut seen = Vec::new();
+        for h in &haps {
+            for (start, end) in (0..h.len()).flat_map(|s| (s + 6..=h.len().min(s + 10)).map(move |e| (s, e))) {
+                let window = Fragment {
+                    contig: "c#hap".into(),
+                    start,
+                    end,
+                    reverse: false,
+                    seq: h.fetch(&mut reference, start, end).unwrap(),
+                    circular_len: None,
+                    origin: Origin::Genome,
+                    umi: None,
+                };
+                let f = h.to_reference(&reference, window);
+                let Origin::Haplotype { index, variants, .. } = &f.origin else { panic!("not a haplotype fragment") };
+                let original = &seq[f.start..f.end];
+                match (*index, variants.as_slice()) {
+                    (_, []) => assert_eq!(f.seq, original),
+                    (1, [snp]) if snp == "snp" => {
+                        assert_eq!(f.seq.len(), original.len());
+                        assert_eq!(f.seq[4 - f.start], b'A');
+                        assert!(f.header(1).contains("hap=1 vars=snp"));
+                    }
+                    (2, [ins]) if ins == "ins" => {
+                        assert!(f.seq.len() <= original.len() + 3);
+                        assert_eq!(f.ref_pos(f.seq.len() - 1), f.end);
+                    }
+                    other => panic!("unexpected {:?}", other),
+                }
+                seen.extend(variants.iter().cloned());
+            }
+        }
+        assert!(seen.iter().any(|v| v == "snp") && seen.iter().any(|v| v == "ins"));
+
+        // A window past the end of a circular haplotype continues from the origin (unrolled), through the SNP
+        let h = &haps[0];
+        let mut bases = h.fetch(&mut reference, h.len() - 3, h.len()).unwrap();
+        bases.extend(h.fetch(&mut reference, 0, 6).unwrap());
+        let window = Fragment {
+            contig: "c#1".into(),
+            start: h.len() - 3,
+            end: h.len() + 6,
+            reverse: false,
+            seq: bases,
+            circular_len: Some(h.len()),
+            origin: Origin::Genome,
+            umi: None,
+        };
+        let f = h.to_reference(&reference, window);
+        assert_eq!((f.start, f.end, f.circular_len), (29, 38, Some(32)));
+        assert_eq!((f.ref_pos(3), f.wrap(f.ref_pos(3))), (33, 1));
+        assert!(f.header(1).contains("hap=1 vars=snp"));
+    }
+}